@@ -0,0 +1,282 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::mesh::{Instance, Mesh};
+use crate::orbit::KeplerOrbit;
+use crate::sphere::{icosphere, projected_world_radius, random_direction};
+use crate::Uniforms;
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use nalgebra_glm::{rotation, scaling, translation, Vec3, Vec4};
+use rand::prelude::*;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+// Distinct rock shapes shared by the whole belt
+const SHAPE_COUNT: usize = 8;
+// Detail levels of each shape, icospheres of 20, 80 and 320 triangles
+const SHAPE_LEVELS: usize = 3;
+// How far the noise pushes the surface in or out, relative to the radius
+const ROCK_ROUGHNESS: f32 = 0.35;
+// Longest triangle edge in pixels a level may show before the next is used
+const EDGE_PIXELS: f32 = 10.0;
+// Rocks under this radius in pixels are drawn as a single point instead of a
+// mesh, and under `CULL_PIXELS` not at all
+const POINT_PIXELS: f32 = 1.5;
+const CULL_PIXELS: f32 = 0.1;
+
+// Main belt in AU, mapped onto the inner and outer radii the belt is built
+// with. Resonances with Jupiter (3:1, 5:2 and 7:3) keep the Kirkwood gaps empty.
+pub const BELT_AU: (f32, f32) = (2.1, 3.3);
+const KIRKWOOD_GAPS_AU: [f32; 3] = [2.50, 2.82, 2.95];
+const GAP_HALF_WIDTH_AU: f32 = 0.025;
+const MAX_ECCENTRICITY: f32 = 0.15;
+// Radians; most rocks stay close to the ecliptic
+const MAX_INCLINATION: f32 = 0.3;
+// How far past the belt's edges an eccentric orbit may reach, in scene units
+const ECCENTRIC_REACH: f32 = 0.6;
+// Mean motion at the inner edge, in radians per unit of `Uniforms::time`
+const INNER_MEAN_MOTION: f32 = 4e-5;
+// Rock radii in scene units, following a power law: many small, few large
+const RADIUS_RANGE: (f32, f32) = (0.02, 0.12);
+const RADIUS_SLOPE: f32 = 2.5;
+// Spin in radians per unit of `Uniforms::time`
+const SPIN_RATE: (f32, f32) = (1e-4, 8e-4);
+
+// Taxonomic classes as (share, color): dark carbonaceous C types, stony S
+// types and metallic M types
+const ROCK_CLASSES: [(f32, Color); 3] = [
+    (0.75, Color::new(140, 135, 128)),
+    (0.17, Color::new(230, 185, 140)),
+    (0.08, Color::new(205, 205, 215)),
+];
+
+struct Asteroid {
+    orbit: KeplerOrbit,
+    shape: usize,
+    radius: f32,
+    spin_axis: Vec3,
+    spin_rate: f32,
+    spin_phase: f32,
+    tint: Color,
+}
+
+pub struct AsteroidBelt {
+    // Focus of every orbit, the Sun
+    center: Vec3,
+    asteroids: Vec<Asteroid>,
+    // Unit-sized rocks, `SHAPE_LEVELS` consecutive meshes per shape from the
+    // coarsest up
+    meshes: Vec<Rc<Mesh>>,
+}
+
+impl AsteroidBelt {
+    // `count` rocks orbiting `center` with semi-major axes between `inner`
+    // and `outer`. The same seed always gives the same belt.
+    pub fn new(center: Vec3, inner: f32, outer: f32, count: usize, seed: u64) -> Self {
+        Self::with_scale(center, inner, outer, count, seed, 1.0, INNER_MEAN_MOTION)
+    }
+
+    // Like `new`, with rocks `rock_scale` times their usual size and orbits
+    // at the inner edge taking `inner_mean_motion` radians per unit of time
+    pub fn with_scale(
+        center: Vec3,
+        inner: f32,
+        outer: f32,
+        count: usize,
+        seed: u64,
+        rock_scale: f32,
+        inner_mean_motion: f32,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let meshes = (0..SHAPE_COUNT)
+            .flat_map(|_| {
+                let axes = Vec3::new(1.0, rng.gen_range(0.55..1.0), rng.gen_range(0.4..0.85));
+                rock_meshes(rng.gen(), &axes)
+            })
+            .collect();
+
+        let mut asteroids = Vec::with_capacity(count);
+        while asteroids.len() < count {
+            let au = rng.gen_range(BELT_AU.0..BELT_AU.1);
+            if KIRKWOOD_GAPS_AU.iter().any(|gap| (au - gap).abs() < GAP_HALF_WIDTH_AU) {
+                continue;
+            }
+            let semi_major_axis = inner + (outer - inner) * (au - BELT_AU.0) / (BELT_AU.1 - BELT_AU.0);
+
+            // Keep perihelion and aphelion from straying far out of the belt
+            let reach = ((semi_major_axis - inner + ECCENTRIC_REACH) / semi_major_axis)
+                .min((outer + ECCENTRIC_REACH - semi_major_axis) / semi_major_axis);
+            let eccentricity = (rng.gen::<f32>().powi(2) * MAX_ECCENTRICITY).min(reach);
+
+            let orbit = KeplerOrbit {
+                semi_major_axis,
+                eccentricity,
+                inclination: rng.gen::<f32>().powi(2) * MAX_INCLINATION,
+                ascending_node: rng.gen::<f32>() * 2.0 * PI,
+                periapsis_argument: rng.gen::<f32>() * 2.0 * PI,
+                mean_anomaly: rng.gen::<f32>() * 2.0 * PI,
+                // Kepler's third law: the period grows with a^(3/2)
+                mean_motion: inner_mean_motion * (semi_major_axis / inner).powf(-1.5),
+            };
+
+            asteroids.push(Asteroid {
+                orbit,
+                shape: rng.gen_range(0..SHAPE_COUNT),
+                radius: random_radius(&mut rng) * rock_scale,
+                spin_axis: random_direction(&mut rng),
+                spin_rate: rng.gen_range(SPIN_RATE.0..SPIN_RATE.1),
+                spin_phase: rng.gen::<f32>() * 2.0 * PI,
+                tint: random_tint(&mut rng),
+            });
+        }
+
+        AsteroidBelt {
+            center,
+            asteroids,
+            meshes,
+        }
+    }
+
+    // Moves the focus of every orbit, for when the scene is recentred
+    pub fn set_center(&mut self, center: Vec3) {
+        self.center = center;
+    }
+
+    // Places every rock for `uniforms.time` and sorts it by what it looks like
+    // from the camera: off screen or too small to see, a single point, or one
+    // of the meshes of its shape by size on screen
+    pub fn visible(&self, uniforms: &Uniforms) -> BeltDraw {
+        let mut batches: Vec<Vec<Instance>> = vec![Vec::new(); self.meshes.len()];
+        let mut points = Vec::new();
+        let (width, height) = (uniforms.viewport_matrix[(0, 3)] * 2.0, uniforms.viewport_matrix[(1, 3)] * 2.0);
+        let view_projection = uniforms.projection_matrix * uniforms.view_matrix;
+
+        for (index, asteroid) in self.asteroids.iter().enumerate() {
+            let position = self.center + asteroid.orbit.position(uniforms.time);
+            let clip = view_projection * Vec4::new(position.x, position.y, position.z, 1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+
+            // Rocks reach past their radius where the noise pushes them out
+            let bounding_radius = asteroid.radius * (1.0 + ROCK_ROUGHNESS);
+            let pixels = projected_world_radius(uniforms, &position, bounding_radius);
+            if !pixels.is_finite() || pixels < CULL_PIXELS {
+                continue;
+            }
+
+            let ndc = clip.xyz() / clip.w;
+            let screen = uniforms.viewport_matrix * Vec4::new(ndc.x, ndc.y, ndc.z, 1.0);
+            if screen.x + pixels < 0.0
+                || screen.y + pixels < 0.0
+                || screen.x - pixels > width
+                || screen.y - pixels > height
+            {
+                continue;
+            }
+
+            if pixels < POINT_PIXELS {
+                let depth = uniforms.depth.depth(ndc.z, clip.w);
+                if (0.0..=1.0).contains(&depth) {
+                    points.push((Vec3::new(screen.x, screen.y, depth), asteroid.tint, pixels));
+                }
+                continue;
+            }
+
+            // Edges of the coarsest level span about 1.1 radii, and halve on
+            // every level after it
+            let level = ((pixels * 1.1 / EDGE_PIXELS).log2().ceil().max(0.0) as usize).min(SHAPE_LEVELS - 1);
+            let spin = asteroid.spin_phase + asteroid.spin_rate * uniforms.time;
+            let model_matrix = translation(&position)
+                * rotation(spin, &asteroid.spin_axis)
+                * scaling(&Vec3::new(asteroid.radius, asteroid.radius, asteroid.radius));
+
+            batches[asteroid.shape * SHAPE_LEVELS + level].push(Instance {
+                model_matrix,
+                tint: asteroid.tint,
+                id: index as u32,
+            });
+        }
+
+        BeltDraw {
+            batches: self
+                .meshes
+                .iter()
+                .zip(batches)
+                .filter(|(_, instances)| !instances.is_empty())
+                .map(|(mesh, instances)| (Rc::clone(mesh), instances))
+                .collect(),
+            points,
+        }
+    }
+}
+
+// One frame of the belt, as returned by `AsteroidBelt::visible`
+pub struct BeltDraw {
+    // Each mesh with the rocks drawn with it, for instanced rendering
+    pub batches: Vec<(Rc<Mesh>, Vec<Instance>)>,
+    // Screen position and depth, tint and radius in pixels of distant rocks
+    points: Vec<(Vec3, Color, f32)>,
+}
+
+impl BeltDraw {
+    // Distant rocks as one depth-tested pixel each, as opaque as the share of
+    // the pixel they would cover
+    pub fn draw_points(&self, framebuffer: &mut Framebuffer) {
+        for (screen, tint, pixels) in &self.points {
+            let coverage = PI * pixels * pixels;
+            // About the brightness of a half-lit rock
+            framebuffer.blend_point(
+                screen.x as i32,
+                screen.y as i32,
+                screen.z,
+                &(*tint * 0.5),
+                coverage,
+            );
+        }
+    }
+}
+
+// Every detail level of one rock: a unit icosphere squashed along `axes` and
+// roughened by noise. The noise is sampled on the unit sphere, so all levels
+// share the same shape.
+fn rock_meshes(seed: i32, axes: &Vec3) -> Vec<Rc<Mesh>> {
+    let mut noise = FastNoiseLite::with_seed(seed);
+    noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+    noise.set_frequency(Some(1.2));
+    noise.set_fractal_type(Some(FractalType::FBm));
+    noise.set_fractal_octaves(Some(3));
+
+    (0..SHAPE_LEVELS)
+        .map(|level| {
+            let mut mesh = icosphere(level, 1.0);
+            for vertex in &mut mesh.vertices {
+                let direction = vertex.position.normalize();
+                let bump = noise.get_noise_3d(direction.x, direction.y, direction.z);
+                vertex.position = direction.component_mul(axes) * (1.0 + ROCK_ROUGHNESS * bump);
+            }
+            mesh.recompute_normals();
+            Rc::new(mesh)
+        })
+        .collect()
+}
+
+// Power law between the smallest and largest radius
+fn random_radius<R: Rng>(rng: &mut R) -> f32 {
+    let (min, max) = (RADIUS_RANGE.0.powf(-RADIUS_SLOPE), RADIUS_RANGE.1.powf(-RADIUS_SLOPE));
+    (min + rng.gen::<f32>() * (max - min)).powf(-1.0 / RADIUS_SLOPE)
+}
+
+// Color of a random class, a little lighter or darker per rock
+fn random_tint<R: Rng>(rng: &mut R) -> Color {
+    let mut pick = rng.gen::<f32>();
+    let mut color = ROCK_CLASSES[0].1;
+    for (share, class_color) in ROCK_CLASSES {
+        if pick < share {
+            color = class_color;
+            break;
+        }
+        pick -= share;
+    }
+    color * rng.gen_range(0.8..1.1)
+}
@@ -0,0 +1,125 @@
+use crate::catalog::equatorial_direction;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::picking::ScreenRays;
+use crate::texture::CubeMap;
+use fastnoise_lite::FastNoiseLite;
+use nalgebra_glm::{Mat4, Vec3};
+
+// Galactic north pole and center in equatorial coordinates (J2000)
+const GALACTIC_POLE: (f32, f32) = (12.857, 27.13);
+const GALACTIC_CENTER: (f32, f32) = (17.761, -28.94);
+
+// Angular half-widths, as sines of the galactic latitude
+const BAND_WIDTH: f32 = 0.2;
+const DUST_WIDTH: f32 = 0.07;
+// Angular radius of the central bulge, in radians
+const BULGE_RADIUS: f32 = 0.45;
+
+const BAND_BRIGHTNESS: f32 = 0.16;
+const NEBULA_BRIGHTNESS: f32 = 0.18;
+
+const WARM_GLOW: Vec3 = Vec3::new(1.0, 0.88, 0.7);
+const COOL_GLOW: Vec3 = Vec3::new(0.72, 0.8, 1.0);
+const NEBULA_RED: Vec3 = Vec3::new(0.75, 0.15, 0.4);
+const NEBULA_TEAL: Vec3 = Vec3::new(0.1, 0.5, 0.6);
+
+// Milky Way band and nebula clouds behind the stars. Everything depends only
+// on the view direction, so the background sits at infinity.
+pub struct Background {
+    // Band structure, dust lanes, nebula density and nebula hue, in that order
+    noises: Vec<FastNoiseLite>,
+    pole: Vec3,
+    center: Vec3,
+    baked: Option<CubeMap>,
+}
+
+impl Background {
+    pub fn new(noises: Vec<FastNoiseLite>) -> Self {
+        assert_eq!(noises.len(), 4, "the background needs four noises");
+
+        Background {
+            noises,
+            pole: equatorial_direction(GALACTIC_POLE.0, GALACTIC_POLE.1),
+            center: equatorial_direction(GALACTIC_CENTER.0, GALACTIC_CENTER.1),
+            baked: None,
+        }
+    }
+
+    // Evaluates the background once per texel of a cube map with `size`
+    // pixels per face edge; rendering then samples it instead of the noise
+    pub fn bake(&mut self, size: usize) {
+        self.baked = Some(CubeMap::from_fn(size, |direction| self.evaluate(&direction)));
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.baked.is_some()
+    }
+
+    // Drops the cube map and goes back to evaluating every pixel
+    pub fn unbake(&mut self) {
+        self.baked = None;
+    }
+
+    pub fn sample(&self, direction: &Vec3) -> Vec3 {
+        match &self.baked {
+            Some(cube_map) => cube_map.sample(direction),
+            None => self.evaluate(direction),
+        }
+    }
+
+    // Procedural color for a unit direction, linear 0..1
+    pub fn evaluate(&self, direction: &Vec3) -> Vec3 {
+        let [band_noise, dust_noise, nebula_noise, hue_noise] = [0, 1, 2, 3].map(|index| &self.noises[index]);
+        let noise = |noise: &FastNoiseLite, scale: f32| {
+            noise.get_noise_3d(direction.x * scale, direction.y * scale, direction.z * scale)
+        };
+
+        // Milky Way: a gaussian band around the galactic equator, brighter and
+        // warmer towards the center and broken up into clouds
+        let latitude = direction.dot(&self.pole);
+        let band = (-(latitude / BAND_WIDTH).powi(2)).exp();
+        let from_center = direction.dot(&self.center).clamp(-1.0, 1.0).acos();
+        let bulge = (-(from_center / BULGE_RADIUS).powi(2)).exp();
+
+        let clouds = 0.35 + 0.65 * (0.5 + 0.5 * noise(band_noise, 1.0));
+        // Dark lanes of dust along the middle of the band
+        let lanes = (1.0 - noise(dust_noise, 1.0).abs()).powi(3);
+        let dust = (-(latitude / DUST_WIDTH).powi(2)).exp() * lanes;
+
+        let glow = (band * (0.6 + 1.4 * bulge) * clouds * (1.0 - 0.85 * dust)).max(0.0);
+        let band_color = COOL_GLOW.lerp(&WARM_GLOW, bulge) * glow * BAND_BRIGHTNESS;
+
+        // Nebulae: sparse clouds where the density noise peaks, domain warped
+        // so they look wispy, tinted between hydrogen red and oxygen teal
+        let warp = noise(hue_noise, 2.0) * 0.35;
+        let warped = direction + Vec3::new(warp, -warp, warp);
+        let density = nebula_noise.get_noise_3d(warped.x, warped.y, warped.z);
+        let nebula = smoothstep(0.35, 0.8, density);
+        let hue = 0.5 + 0.5 * noise(hue_noise, 0.7);
+        let nebula_color = NEBULA_TEAL.lerp(&NEBULA_RED, hue) * nebula * NEBULA_BRIGHTNESS;
+
+        band_color + nebula_color
+    }
+
+    // Fills every pixel with the background seen through it. Meant to run
+    // right after clearing, before the stars; the depth buffer is not touched.
+    pub fn render(&self, framebuffer: &mut Framebuffer, view: &Mat4, projection: &Mat4) {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        let Some(rays) = ScreenRays::new(width, height, view, projection) else {
+            return;
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = self.sample(&rays.direction(x, y));
+                framebuffer.buffer[y * width + x] = Color::from_float(color.x, color.y, color.z).to_hex();
+            }
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,228 @@
+use crate::color::Color;
+use crate::texture::{CubeFace, CubeMap, Texture, CUBE_FACES};
+use crate::Uniforms;
+use nalgebra_glm::{Vec2, Vec3};
+use std::f32::consts::PI;
+use std::fs;
+
+// Static layers of a planet shader, evaluated at a point of the model-space
+// sphere. Lighting and animated layers stay in the runtime shader.
+pub type SurfaceFn = fn(&Vec3, &Uniforms) -> Color;
+pub type HeightFn = fn(&Vec3, &Uniforms) -> f32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BakeLayout {
+    Equirectangular,
+    CubeMap,
+}
+
+pub struct BakeSettings {
+    pub layout: BakeLayout,
+    // Width of the equirectangular map (height is half) or size of each cube face
+    pub resolution: usize,
+    // Radius of the sphere model the shader was written for
+    pub radius: f32,
+    // How much a unit of height per texel tilts the baked normals
+    pub normal_strength: f32,
+}
+
+// Color, height and tangent-space normal maps of one body
+pub struct BakedMaps {
+    pub color: Texture,
+    pub height: Texture,
+    pub normal: Texture,
+}
+
+pub enum Baked {
+    Equirectangular(BakedMaps),
+    CubeMap {
+        color: CubeMap,
+        height: CubeMap,
+        normal: CubeMap,
+    },
+}
+
+pub fn bake(
+    surface_fn: SurfaceFn,
+    height_fn: Option<HeightFn>,
+    uniforms: &Uniforms,
+    settings: &BakeSettings,
+) -> Baked {
+    let sample = |direction: Vec3| {
+        let position = direction * settings.radius;
+        let color = surface_fn(&position, uniforms).to_vec3();
+        // Without a height function the surface is flat
+        let height = height_fn.map(|f| f(&position, uniforms)).unwrap_or(0.5);
+        (color, height)
+    };
+
+    match settings.layout {
+        BakeLayout::Equirectangular => {
+            let width = settings.resolution;
+            let height = (settings.resolution / 2).max(1);
+            let maps = bake_grid(width, height, true, settings.normal_strength, |uv| {
+                sample(equirectangular_direction(uv))
+            });
+            Baked::Equirectangular(maps)
+        }
+        BakeLayout::CubeMap => {
+            let size = settings.resolution;
+            let faces: Vec<BakedMaps> = CUBE_FACES
+                .iter()
+                .map(|face| {
+                    bake_grid(size, size, false, settings.normal_strength, |uv| {
+                        sample(face.direction(uv))
+                    })
+                })
+                .collect();
+
+            let mut color = Vec::new();
+            let mut height = Vec::new();
+            let mut normal = Vec::new();
+            for maps in faces {
+                color.push(maps.color);
+                height.push(maps.height);
+                normal.push(maps.normal);
+            }
+
+            Baked::CubeMap {
+                color: CubeMap::from_faces(color),
+                height: CubeMap::from_faces(height),
+                normal: CubeMap::from_faces(normal),
+            }
+        }
+    }
+}
+
+// Inverse of texture::equirectangular_uv
+pub fn equirectangular_direction(uv: Vec2) -> Vec3 {
+    let longitude = (uv.x - 0.5) * 2.0 * PI;
+    let colatitude = uv.y * PI;
+    Vec3::new(
+        colatitude.sin() * longitude.cos(),
+        colatitude.cos(),
+        colatitude.sin() * longitude.sin(),
+    )
+}
+
+// Resamples a cube map bake into an equirectangular map `width` texels wide,
+// the only layout the runtime shaders sample
+pub fn cube_to_equirectangular(cube: &CubeMap, width: usize) -> Texture {
+    Texture::from_fn(width, (width / 2).max(1), |uv| cube.sample(&equirectangular_direction(uv)))
+}
+
+// Like `cube_to_equirectangular` for tangent-space normal maps: each normal is
+// moved from the tangent frame of its cube face to the equirectangular one
+pub fn cube_normals_to_equirectangular(cube: &CubeMap, width: usize) -> Texture {
+    Texture::from_fn(width, (width / 2).max(1), |uv| {
+        let direction = equirectangular_direction(uv);
+        let (face, face_uv) = CubeFace::from_direction(&direction);
+        let local = cube.sample(&direction) * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+        let (tangent, bitangent) = face_tangents(face, face_uv, &direction);
+        let normal = tangent * local.x + bitangent * local.y + direction * local.z;
+
+        // Derivatives of equirectangular_direction along u and v
+        let longitude = (uv.x - 0.5) * 2.0 * PI;
+        let colatitude = uv.y * PI;
+        let (tangent, bitangent) = tangent_frame(
+            &direction,
+            &Vec3::new(-longitude.sin(), 0.0, longitude.cos()),
+            &Vec3::new(
+                colatitude.cos() * longitude.cos(),
+                -colatitude.sin(),
+                colatitude.cos() * longitude.sin(),
+            ),
+        );
+        let local = Vec3::new(normal.dot(&tangent), normal.dot(&bitangent), normal.dot(&direction));
+        local.normalize() * 0.5 + Vec3::new(0.5, 0.5, 0.5)
+    })
+}
+
+// Tangent frame of a cube face at `uv`, from central differences of its
+// directions
+fn face_tangents(face: CubeFace, uv: Vec2, normal: &Vec3) -> (Vec3, Vec3) {
+    let step = 1e-3;
+    let du = face.direction(uv + Vec2::new(step, 0.0)) - face.direction(uv - Vec2::new(step, 0.0));
+    let dv = face.direction(uv + Vec2::new(0.0, step)) - face.direction(uv - Vec2::new(0.0, step));
+    tangent_frame(normal, &du, &dv)
+}
+
+// Orthonormal tangent and bitangent closest to the surface derivatives along
+// u and v
+fn tangent_frame(normal: &Vec3, du: &Vec3, dv: &Vec3) -> (Vec3, Vec3) {
+    let tangent = (du - normal * normal.dot(du)).normalize();
+    let bitangent = dv - normal * normal.dot(dv);
+    let bitangent = (bitangent - tangent * tangent.dot(&bitangent)).normalize();
+    (tangent, bitangent)
+}
+
+fn bake_grid<F>(width: usize, height: usize, wrap_u: bool, normal_strength: f32, sample: F) -> BakedMaps
+where
+    F: Fn(Vec2) -> (Vec3, f32),
+{
+    let mut colors = Vec::with_capacity(width * height);
+    let mut heights = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let uv = Vec2::new(
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            );
+            let (color, h) = sample(uv);
+            colors.push(color);
+            heights.push(h);
+        }
+    }
+
+    let height_at = |x: i64, y: i64| {
+        let x = if wrap_u {
+            x.rem_euclid(width as i64)
+        } else {
+            x.clamp(0, width as i64 - 1)
+        };
+        let y = y.clamp(0, height as i64 - 1);
+        heights[y as usize * width + x as usize]
+    };
+
+    // Central differences of the height field, encoded as 0.5 + 0.5 * n with
+    // +X along u, +Y along v and +Z out of the surface
+    let mut normals = Vec::with_capacity(width * height);
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let du = (height_at(x + 1, y) - height_at(x - 1, y)) * 0.5;
+            let dv = (height_at(x, y + 1) - height_at(x, y - 1)) * 0.5;
+            let normal = Vec3::new(-du * normal_strength, -dv * normal_strength, 1.0).normalize();
+            normals.push(normal * 0.5 + Vec3::new(0.5, 0.5, 0.5));
+        }
+    }
+
+    let heights = heights.iter().map(|&h| Vec3::new(h, h, h)).collect();
+
+    BakedMaps {
+        color: Texture::from_texels(width, height, colors),
+        height: Texture::from_texels(width, height, heights),
+        normal: Texture::from_texels(width, height, normals),
+    }
+}
+
+impl Baked {
+    // Files go to `<dir>/<name>_color.png`, `_height` and `_normal`; cube maps
+    // add the face suffix
+    pub fn save(&self, dir: &str, name: &str) -> Result<(), image::ImageError> {
+        fs::create_dir_all(dir).map_err(image::ImageError::IoError)?;
+
+        match self {
+            Baked::Equirectangular(maps) => {
+                maps.color.save(&format!("{}/{}_color.png", dir, name))?;
+                maps.height.save(&format!("{}/{}_height.png", dir, name))?;
+                maps.normal.save(&format!("{}/{}_normal.png", dir, name))
+            }
+            Baked::CubeMap { color, height, normal } => {
+                color.save(dir, &format!("{}_color", name))?;
+                height.save(dir, &format!("{}_height", name))?;
+                normal.save(dir, &format!("{}_normal", name))
+            }
+        }
+    }
+}
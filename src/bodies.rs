@@ -0,0 +1,536 @@
+use crate::orbit::{eccentric_anomaly, kepler_offset, Orbit};
+use crate::sphere::SPHERE_RADIUS;
+use nalgebra_glm::{DVec3, Vec3};
+use std::f64::consts::PI;
+
+// Kilometres in an astronomical unit
+pub const AU: f64 = 149_597_870.7;
+// Simulated seconds per unit of `Uniforms::time`, whose 0 is the J2000 epoch
+const SECONDS_PER_TIME_UNIT: f64 = 10.0;
+// Days
+const SIDEREAL_YEAR: f64 = 365.256;
+
+// True scale: one scene unit per AU, sizes included
+const TRUE_UNITS_PER_AU: f64 = 1.0;
+// Logarithmic scale: distances from the Sun grow by `LOG_DISTANCE_STEP`
+// units every time they double, counted from `LOG_REFERENCE_AU`. Sizes and
+// distances around a planet keep their true proportions, magnified so Jupiter
+// is about one unit across.
+const LOG_DISTANCE_STEP: f64 = 12.0;
+const LOG_REFERENCE_AU: f64 = 0.1;
+const LOG_UNITS_PER_KM: f64 = 1.4e-5;
+// Earth's radius, which the visual layout's sizes are relative to: it is
+// drawn with scale 1
+const VISUAL_UNITS_PER_KM: f64 = SPHERE_RADIUS as f64 / 6371.0;
+
+// Where each body is drawn, and how big
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScaleMode {
+    // Hand-picked positions and sizes, everything in a row and easy to see
+    Visual,
+    // Real sizes and distances, moving on their real orbits
+    True,
+    // Real orbits with distances from the Sun compressed logarithmically
+    Logarithmic,
+}
+
+impl ScaleMode {
+    pub fn next(self) -> Self {
+        match self {
+            ScaleMode::Visual => ScaleMode::True,
+            ScaleMode::True => ScaleMode::Logarithmic,
+            ScaleMode::Logarithmic => ScaleMode::Visual,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScaleMode::Visual => "visual",
+            ScaleMode::True => "real",
+            ScaleMode::Logarithmic => "logarítmica",
+        }
+    }
+
+    // Scene units per km, for sizes and distances around a planet
+    pub fn units_per_km(self) -> f64 {
+        match self {
+            ScaleMode::Visual => VISUAL_UNITS_PER_KM,
+            ScaleMode::True => TRUE_UNITS_PER_AU / AU,
+            ScaleMode::Logarithmic => LOG_UNITS_PER_KM,
+        }
+    }
+
+    // Scene distance of something `au` AU from the Sun. The visual layout
+    // does not follow distances, so it uses the true scale.
+    pub fn solar_distance(self, au: f64) -> f64 {
+        match self {
+            ScaleMode::Visual | ScaleMode::True => au * TRUE_UNITS_PER_AU,
+            ScaleMode::Logarithmic => LOG_DISTANCE_STEP * (1.0 + au / LOG_REFERENCE_AU).log2(),
+        }
+    }
+
+    // Offset from the Sun in km to scene units, keeping its direction
+    fn solar_offset(self, offset: &DVec3) -> DVec3 {
+        let au = offset.magnitude() / AU;
+        if au == 0.0 {
+            return DVec3::zeros();
+        }
+        offset * (self.solar_distance(au) / (au * AU))
+    }
+}
+
+// Orbit of a body around its parent
+pub struct OrbitalElements {
+    // Index in `BODIES` of the body orbited
+    pub parent: usize,
+    // km
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    // Degrees, to the ecliptic or, for `equatorial` orbits, to the parent's
+    // equator
+    pub inclination: f64,
+    pub ascending_node: f64,
+    pub periapsis_argument: f64,
+    // Degrees at J2000
+    pub mean_anomaly: f64,
+    // Sidereal period in days
+    pub period: f64,
+    pub equatorial: bool,
+}
+
+pub struct BodyData {
+    pub name: &'static str,
+    // Mean radius in km and mass in kg
+    pub radius: f64,
+    pub mass: f64,
+    // Sidereal rotation period in hours
+    pub rotation_period: f64,
+    // Degrees between the rotation axis and the orbit normal; bodies tilted
+    // past 90° spin backwards
+    pub axial_tilt: f64,
+    pub orbit: Option<OrbitalElements>,
+}
+
+pub const BODY_COUNT: usize = 14;
+// `BODIES` starts with the Sun, which every chain of parents leads back to
+pub const SUN: usize = 0;
+// Places in `BODIES` of the planets with moons
+const EARTH: usize = 3;
+const MARS: usize = 5;
+
+// Every body of the scene but the comet, in the order of the focus list.
+// Planets have J2000 mean elements; Eris and Sedna's are brought back to
+// J2000 from later epochs, and their tilts, like Sedna's mass, are unknown.
+pub const BODIES: [BodyData; BODY_COUNT] = [
+    BodyData {
+        name: "Sol",
+        radius: 695_700.0,
+        mass: 1.9885e30,
+        rotation_period: 609.12,
+        axial_tilt: 7.25,
+        orbit: None,
+    },
+    BodyData {
+        name: "Mercurio",
+        radius: 2_439.7,
+        mass: 3.3011e23,
+        rotation_period: 1_407.6,
+        axial_tilt: 0.03,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 0.387_10 * AU,
+            eccentricity: 0.205_63,
+            inclination: 7.005,
+            ascending_node: 48.331,
+            periapsis_argument: 29.125,
+            mean_anomaly: 174.795,
+            period: 87.969,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Venus",
+        radius: 6_051.8,
+        mass: 4.8675e24,
+        rotation_period: 5_832.6,
+        axial_tilt: 177.36,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 0.723_33 * AU,
+            eccentricity: 0.006_77,
+            inclination: 3.395,
+            ascending_node: 76.680,
+            periapsis_argument: 54.853,
+            mean_anomaly: 50.447,
+            period: 224.701,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Tierra",
+        radius: 6_371.0,
+        mass: 5.9722e24,
+        rotation_period: 23.934_5,
+        axial_tilt: 23.44,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 1.000_00 * AU,
+            eccentricity: 0.016_71,
+            inclination: 0.0,
+            ascending_node: -11.261,
+            periapsis_argument: 114.208,
+            mean_anomaly: 357.517,
+            period: SIDEREAL_YEAR,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Luna",
+        radius: 1_737.4,
+        mass: 7.342e22,
+        rotation_period: 655.72,
+        axial_tilt: 6.68,
+        orbit: Some(OrbitalElements {
+            parent: EARTH,
+            semi_major_axis: 384_400.0,
+            eccentricity: 0.054_9,
+            inclination: 5.145,
+            ascending_node: 125.08,
+            periapsis_argument: 318.15,
+            mean_anomaly: 135.27,
+            period: 27.321_7,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Marte",
+        radius: 3_389.5,
+        mass: 6.4171e23,
+        rotation_period: 24.622_9,
+        axial_tilt: 25.19,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 1.523_68 * AU,
+            eccentricity: 0.093_40,
+            inclination: 1.851,
+            ascending_node: 49.578,
+            periapsis_argument: 286.463,
+            mean_anomaly: 19.412,
+            period: 686.980,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Fobos",
+        radius: 11.27,
+        mass: 1.0659e16,
+        rotation_period: 7.653_8,
+        axial_tilt: 0.0,
+        orbit: Some(OrbitalElements {
+            parent: MARS,
+            semi_major_axis: 9_376.0,
+            eccentricity: 0.015_1,
+            inclination: 1.075,
+            ascending_node: 207.784,
+            periapsis_argument: 150.057,
+            mean_anomaly: 91.059,
+            period: 0.318_91,
+            equatorial: true,
+        }),
+    },
+    BodyData {
+        name: "Júpiter",
+        radius: 69_911.0,
+        mass: 1.8982e27,
+        rotation_period: 9.925,
+        axial_tilt: 3.13,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 5.202_60 * AU,
+            eccentricity: 0.048_49,
+            inclination: 1.303,
+            ascending_node: 100.464,
+            periapsis_argument: 273.867,
+            mean_anomaly: 20.065,
+            period: 4_332.59,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Saturno",
+        radius: 58_232.0,
+        mass: 5.6834e26,
+        rotation_period: 10.656,
+        axial_tilt: 26.73,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 9.554_91 * AU,
+            eccentricity: 0.055_51,
+            inclination: 2.489,
+            ascending_node: 113.666,
+            periapsis_argument: 338.771,
+            mean_anomaly: 317.513,
+            period: 10_759.22,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Urano",
+        radius: 25_362.0,
+        mass: 8.6810e25,
+        rotation_period: 17.24,
+        axial_tilt: 97.77,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 19.218_45 * AU,
+            eccentricity: 0.046_30,
+            inclination: 0.773,
+            ascending_node: 74.006,
+            periapsis_argument: 98.999,
+            mean_anomaly: 140.227,
+            period: 30_688.5,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Neptuno",
+        radius: 24_622.0,
+        mass: 1.02413e26,
+        rotation_period: 16.11,
+        axial_tilt: 28.32,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 30.110_39 * AU,
+            eccentricity: 0.008_99,
+            inclination: 1.769,
+            ascending_node: 131.784,
+            periapsis_argument: 276.340,
+            mean_anomaly: 256.756,
+            period: 60_182.0,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Plutón",
+        radius: 1_188.3,
+        mass: 1.303e22,
+        rotation_period: 153.293,
+        axial_tilt: 122.53,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 39.482 * AU,
+            eccentricity: 0.248_8,
+            inclination: 17.14,
+            ascending_node: 110.30,
+            periapsis_argument: 113.77,
+            mean_anomaly: 14.86,
+            period: 90_560.0,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Eris",
+        radius: 1_163.0,
+        mass: 1.6466e22,
+        rotation_period: 378.87,
+        axial_tilt: 0.0,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 67.864 * AU,
+            eccentricity: 0.436_07,
+            inclination: 44.040,
+            ascending_node: 35.951,
+            periapsis_argument: 151.639,
+            mean_anomaly: 192.8,
+            period: 203_830.0,
+            equatorial: false,
+        }),
+    },
+    BodyData {
+        name: "Sedna",
+        radius: 500.0,
+        mass: 1e21,
+        rotation_period: 10.273,
+        axial_tilt: 0.0,
+        orbit: Some(OrbitalElements {
+            parent: SUN,
+            semi_major_axis: 506.0 * AU,
+            eccentricity: 0.849_6,
+            inclination: 11.93,
+            ascending_node: 144.25,
+            periapsis_argument: 311.29,
+            mean_anomaly: 357.5,
+            period: 4_160_000.0,
+            equatorial: false,
+        }),
+    },
+];
+
+impl BodyData {
+    // Index in `BODIES` of the body this one orbits, none for the Sun
+    pub fn parent(&self) -> Option<usize> {
+        self.orbit.as_ref().map(|orbit| orbit.parent)
+    }
+}
+
+// Index in `BODIES` of the body called `name`
+pub fn body_index(name: &str) -> Option<usize> {
+    BODIES.iter().position(|body| body.name == name)
+}
+
+// Whether body `index` is the Sun or orbits it directly, unlike the moons
+pub fn is_primary(index: usize) -> bool {
+    BODIES[index].parent().map_or(true, |parent| parent == SUN)
+}
+
+// Mean motion of a small body `au` AU from the Sun, in radians per unit of
+// `Uniforms::time`, from Kepler's third law and Earth's year
+pub fn solar_mean_motion(au: f64) -> f32 {
+    (2.0 * PI / time_units(SIDEREAL_YEAR * au.powf(1.5))) as f32
+}
+
+// Units of `Uniforms::time` in `days`
+fn time_units(days: f64) -> f64 {
+    days * 86_400.0 / SECONDS_PER_TIME_UNIT
+}
+
+// Every body of `BODIES` placed at one moment for the true or logarithmic
+// scale. Positions are kept in f64 relative to the Sun and only brought into
+// the scene's f32 relative to an origin body, so the one the camera is on
+// keeps its shape and stays still however far out it is.
+pub struct Layout {
+    mode: ScaleMode,
+    offsets: [DVec3; BODY_COUNT],
+    eccentric_anomalies: [f64; BODY_COUNT],
+    scales: [f32; BODY_COUNT],
+    rotations: [Vec3; BODY_COUNT],
+    // Scene position of the Sun when it is the origin
+    sun: Vec3,
+    origin: usize,
+}
+
+impl Layout {
+    pub fn new(mode: ScaleMode, time: f32, sun: Vec3) -> Self {
+        let time = time as f64;
+        let units_per_km = mode.units_per_km();
+        let mut layout = Layout {
+            mode,
+            offsets: [DVec3::zeros(); BODY_COUNT],
+            eccentric_anomalies: [0.0; BODY_COUNT],
+            scales: [0.0; BODY_COUNT],
+            rotations: [Vec3::zeros(); BODY_COUNT],
+            sun,
+            origin: SUN,
+        };
+
+        // Parents come before their moons in `BODIES`
+        for (index, body) in BODIES.iter().enumerate() {
+            layout.scales[index] = (body.radius * units_per_km) as f32 / SPHERE_RADIUS;
+            // Spin about the body's own axis, then tilt it towards -X
+            let spin = (2.0 * PI * time / time_units(body.rotation_period / 24.0)).rem_euclid(2.0 * PI);
+            layout.rotations[index] = Vec3::new(0.0, spin as f32, body.axial_tilt.to_radians() as f32);
+
+            if let Some(orbit) = &body.orbit {
+                let mean_anomaly = orbit.mean_anomaly.to_radians() + 2.0 * PI * time / time_units(orbit.period);
+                let eccentric_anomaly = eccentric_anomaly(mean_anomaly.rem_euclid(2.0 * PI), orbit.eccentricity);
+                layout.eccentric_anomalies[index] = eccentric_anomaly;
+                layout.offsets[index] = layout.orbit_offset(orbit, eccentric_anomaly);
+            }
+        }
+        layout
+    }
+
+    // Offset from the Sun in scene units of a body on `orbit` at
+    // `eccentric_anomaly`
+    fn orbit_offset(&self, orbit: &OrbitalElements, eccentric_anomaly: f64) -> DVec3 {
+        let mut offset = kepler_offset(
+            orbit.semi_major_axis,
+            orbit.eccentricity,
+            orbit.inclination.to_radians(),
+            orbit.ascending_node.to_radians(),
+            orbit.periapsis_argument.to_radians(),
+            eccentric_anomaly,
+        );
+        if orbit.equatorial {
+            offset = tilt(&offset, BODIES[orbit.parent].axial_tilt.to_radians());
+        }
+
+        if orbit.parent == SUN {
+            self.mode.solar_offset(&offset)
+        } else {
+            self.offsets[orbit.parent] + offset * self.mode.units_per_km()
+        }
+    }
+
+    // Centres the scene on body `origin`: it is drawn at the scene's origin,
+    // or at the Sun's usual place when `origin` is the Sun. Returns how far
+    // everything moved.
+    pub fn recentre(&mut self, origin: usize) -> Vec3 {
+        let before = self.position(SUN);
+        self.origin = origin;
+        self.position(SUN) - before
+    }
+
+    pub fn position(&self, index: usize) -> Vec3 {
+        self.to_scene(&self.offsets[index])
+    }
+
+    // Scale and rotation for `create_model_matrix`
+    pub fn scale(&self, index: usize) -> f32 {
+        self.scales[index]
+    }
+
+    pub fn rotation(&self, index: usize) -> Vec3 {
+        self.rotations[index]
+    }
+
+    // Path of body `index` from where it is now, with its parent held still.
+    // Points are even in eccentric anomaly, so they stay close together where
+    // eccentric orbits swing fast past periapsis.
+    pub fn orbit(&self, index: usize) -> Option<Orbit> {
+        let orbit = BODIES[index].orbit.as_ref()?;
+        Some(Orbit::traced(|turn| {
+            let eccentric_anomaly = self.eccentric_anomalies[index] + 2.0 * PI * turn as f64;
+            self.to_scene(&self.orbit_offset(orbit, eccentric_anomaly))
+        }))
+    }
+
+    // Offset from the Sun to scene position, the difference with the origin
+    // taken before dropping to f32
+    fn to_scene(&self, offset: &DVec3) -> Vec3 {
+        let anchor = if self.origin == SUN { self.sun } else { Vec3::zeros() };
+        let offset = offset - self.offsets[self.origin];
+        anchor + Vec3::new(offset.x as f32, offset.y as f32, offset.z as f32)
+    }
+}
+
+// Rotates `offset` about Z like the tilt in `Layout::rotation`, taking the
+// scene's XZ plane to a tilted body's equator
+fn tilt(offset: &DVec3, angle: f64) -> DVec3 {
+    let (sin, cos) = angle.sin_cos();
+    DVec3::new(offset.x * cos - offset.y * sin, offset.x * sin + offset.y * cos, offset.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parents_are_listed_before_their_moons() {
+        assert_eq!(BODIES[SUN].parent(), None);
+        for (index, body) in BODIES.iter().enumerate().skip(1) {
+            let parent = body.parent().expect(body.name);
+            assert!(parent < index, "{} orbits a later body", body.name);
+        }
+    }
+
+    #[test]
+    fn moon_parents_name_the_planets() {
+        assert_eq!(BODIES[EARTH].name, "Tierra");
+        assert_eq!(BODIES[MARS].name, "Marte");
+        assert_eq!(body_index("Luna").and_then(|index| BODIES[index].parent()), Some(EARTH));
+        assert_eq!(body_index("Fobos").and_then(|index| BODIES[index].parent()), Some(MARS));
+        assert!(!is_primary(body_index("Luna").unwrap()));
+        assert!(is_primary(EARTH));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::bake::HeightFn;
+use crate::fragment::Fragment;
+use crate::texture::{equirectangular_uv, Texture};
+use crate::Uniforms;
+use nalgebra_glm::{mat4_to_mat3, Vec3};
+use std::f32::consts::PI;
+
+// Step used for the finite differences, in model units (planet spheres have radius 0.5)
+const GRADIENT_EPSILON: f32 = 1e-3;
+
+// Perturbs the interpolated normal with the gradient of a height function
+// evaluated around the fragment's model-space position. `strength` scales how
+// much a unit of height per model unit tilts the normal.
+//
+// The model matrices in this renderer only use uniform scale, so the model
+// matrix rotation is enough to move directions between model and world space.
+pub fn bump_normal(fragment: &Fragment, uniforms: &Uniforms, height_fn: HeightFn, strength: f32) -> Vec3 {
+    let model = mat4_to_mat3(&uniforms.model_matrix);
+    let normal = (model.transpose() * fragment.normal).normalize();
+
+    let (_, gradient) = height_and_gradient(&fragment.vertex_position, GRADIENT_EPSILON, |position| {
+        height_fn(position, uniforms)
+    });
+    let bumped = tilt_normal(&normal, &gradient, strength);
+
+    (model * bumped).normalize()
+}
+
+// Where displacement heights come from
+#[derive(Clone, Copy)]
+pub enum HeightSource<'a> {
+    Function(HeightFn),
+    // Equirectangular height map baked with `cargo run -- bake`
+    Map(&'a Texture),
+}
+
+impl<'a> HeightSource<'a> {
+    // The baked map if there is one, otherwise the function it is baked from
+    pub fn baked_or(map: Option<&'a Texture>, height_fn: HeightFn) -> Self {
+        match map {
+            Some(map) => HeightSource::Map(map),
+            None => HeightSource::Function(height_fn),
+        }
+    }
+}
+
+// Moves vertices along their normal by a height function or map, for relief
+// that actually changes the silhouette. Heights are centered on 0.5.
+#[derive(Clone, Copy)]
+pub struct Displacement<'a> {
+    pub height: HeightSource<'a>,
+    // Model units the surface moves for a height change of 1.0
+    pub amplitude: f32,
+}
+
+impl Displacement<'_> {
+    // Displaced model-space position and the normal of the displaced surface
+    pub fn apply(&self, position: &Vec3, normal: &Vec3, uniforms: &Uniforms) -> (Vec3, Vec3) {
+        let normal = normal.normalize();
+        let (h, gradient) = match self.height {
+            HeightSource::Function(height_fn) => {
+                height_and_gradient(position, GRADIENT_EPSILON, |position| height_fn(position, uniforms))
+            }
+            // Steps of about a texel; shorter ones only see the creases of the
+            // bilinear filter
+            HeightSource::Map(map) => {
+                let epsilon = 2.0 * PI * position.magnitude() / map.width() as f32;
+                height_and_gradient(position, epsilon, |position| map.sample(equirectangular_uv(position)).x)
+            }
+        };
+
+        let displaced = position + normal * (h - 0.5) * self.amplitude;
+        (displaced, tilt_normal(&normal, &gradient, self.amplitude))
+    }
+}
+
+fn height_and_gradient<F>(position: &Vec3, epsilon: f32, height_at: F) -> (f32, Vec3)
+where
+    F: Fn(&Vec3) -> f32,
+{
+    let h = height_at(position);
+    let gradient = Vec3::new(
+        height_at(&(position + Vec3::x() * epsilon)) - h,
+        height_at(&(position + Vec3::y() * epsilon)) - h,
+        height_at(&(position + Vec3::z() * epsilon)) - h,
+    ) / epsilon;
+
+    (h, gradient)
+}
+
+// Only the part of the gradient along the surface tilts the normal
+fn tilt_normal(normal: &Vec3, gradient: &Vec3, strength: f32) -> Vec3 {
+    let surface_gradient = gradient - normal * normal.dot(gradient);
+    (normal - surface_gradient * strength).normalize()
+}
+
+// Normal from a tangent-space normal map (+X along u, +Y along v, +Z out of
+// the surface), like the ones written by `cargo run -- bake`
+pub fn normal_map_normal(fragment: &Fragment, normal_map: &Texture) -> Vec3 {
+    let normal = fragment.normal.normalize();
+    let tangent = fragment.tangent.xyz();
+    let tangent = tangent - normal * normal.dot(&tangent);
+    if tangent.magnitude() < 1e-6 {
+        return normal;
+    }
+    let tangent = tangent.normalize();
+    let bitangent = normal.cross(&tangent) * fragment.tangent.w;
+
+    let texel = normal_map.sample_footprint(fragment.tex_coords, fragment.uv_footprint);
+    let local = texel * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+
+    (tangent * local.x + bitangent * local.y + normal * local.z.max(0.0)).normalize()
+}
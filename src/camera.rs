@@ -1,13 +1,34 @@
-use nalgebra_glm::{Vec3, rotate_vec3};
+use nalgebra_glm::{Mat4, Vec3, rotate_vec3};
+use crate::projection::{projection_matrix, DepthMode, DepthRange, Projection};
 use std::f32::consts::PI;
 
 pub struct Camera {
   pub eye: Vec3,
   pub center: Vec3,
   pub up: Vec3,
-  pub has_changed: bool
+  pub has_changed: bool,
+  // Free-fly state: current velocity and the top speed the keys aim for
+  pub velocity: Vec3,
+  pub fly_speed: f32,
+  // Vertical field of view in radians. The original projection passed it as
+  // the aspect ratio and the aspect ratio as the angle, which drew 57°
+  // vertically with planets stretched sideways; the 45° default keeps about
+  // the same horizontal extent with round planets.
+  pub fov: f32,
+  pub near: f32,
+  pub far: f32,
+  pub projection: Projection,
+  // World units visible vertically in orthographic mode
+  pub ortho_height: f32,
+  pub depth_mode: DepthMode,
 }
 
+// How quickly the free-fly velocity reaches its target, per second
+const FLY_RESPONSE: f32 = 4.0;
+// Below this near plane standard depth has no precision left for the far
+// plane ratio, and the camera switches to reversed depth
+const STANDARD_DEPTH_MIN_NEAR: f32 = 1e-3;
+
 impl Camera {
   pub fn new(eye: Vec3, center: Vec3, up: Vec3) -> Self {
     Camera {
@@ -15,6 +36,14 @@ impl Camera {
       center,
       up,
       has_changed: true,
+      velocity: Vec3::new(0.0, 0.0, 0.0),
+      fly_speed: 5.0,
+      fov: 45.0 * PI / 180.0,
+      near: 0.1,
+      far: 1000.0,
+      projection: Projection::Perspective,
+      ortho_height: 60.0,
+      depth_mode: DepthMode::Standard,
     }
   }
 
@@ -75,6 +104,84 @@ impl Camera {
     self.has_changed = true;
   }
 
+  // Moves a fraction `t` of the way to the given view, for cameras that follow
+  // something without snapping to it
+  pub fn ease_towards(&mut self, eye: Vec3, center: Vec3, up: Vec3, t: f32) {
+    let t = t.clamp(0.0, 1.0);
+    self.eye += (eye - self.eye) * t;
+    self.center += (center - self.center) * t;
+    self.up = (self.up + (up - self.up) * t).normalize();
+    self.has_changed = true;
+  }
+
+  // Free-fly: turns the view around the eye. Pitch carries `up` along, so
+  // looking past the vertical does not flip the camera.
+  pub fn look(&mut self, delta_yaw: f32, delta_pitch: f32) {
+    let forward = self.center - self.eye;
+    let distance = forward.magnitude();
+    let right = forward.cross(&self.up).normalize();
+    let up = right.cross(&forward).normalize();
+
+    let turned = rotate_vec3(&forward, delta_yaw, &up);
+    let pitched = rotate_vec3(&turned, delta_pitch, &right);
+
+    self.up = rotate_vec3(&up, delta_pitch, &right);
+    self.center = self.eye + pitched.normalize() * distance;
+    self.has_changed = true;
+  }
+
+  pub fn roll(&mut self, angle: f32) {
+    let forward = (self.center - self.eye).normalize();
+    self.up = rotate_vec3(&self.up, angle, &forward);
+    self.has_changed = true;
+  }
+
+  // Free-fly translation. `input` is in view space (x right, y up, -z
+  // forward); the velocity eases towards it so starts and stops are smooth.
+  pub fn fly(&mut self, input: Vec3, delta_time: f32) {
+    let target = if input.magnitude() > 0.0 {
+      self.basis_change(&input) * self.fly_speed
+    } else {
+      Vec3::new(0.0, 0.0, 0.0)
+    };
+
+    self.velocity += (target - self.velocity) * (1.0 - (-FLY_RESPONSE * delta_time).exp());
+
+    let step = self.velocity * delta_time;
+    if step.magnitude() > 0.0 {
+      self.eye += step;
+      self.center += step;
+      self.has_changed = true;
+    }
+  }
+
+  pub fn projection_matrix(&self, width: f32, height: f32) -> Mat4 {
+    projection_matrix(self.projection, &self.depth_range(), width / height, self.fov, self.ortho_height)
+  }
+
+  pub fn depth_range(&self) -> DepthRange {
+    // Orthographic w is always 1, so there is no distance to take the log of
+    let mode = match (self.projection, self.depth_mode) {
+      (Projection::Orthographic, DepthMode::Logarithmic) => DepthMode::Standard,
+      // With the near plane pulled in to a small body's surface, hyperbolic
+      // depth in f32 squashes everything past a few units into the same
+      // values. Reversed depth puts the float precision on the distant ones
+      // instead. Scaling `far` down with `near` would clip the outer planets.
+      (Projection::Perspective, DepthMode::Standard) if self.near < STANDARD_DEPTH_MIN_NEAR => {
+        DepthMode::Reversed
+      }
+      (_, mode) => mode,
+    };
+
+    DepthRange { mode, near: self.near, far: self.far }
+  }
+
+  // Zoom lens: narrows or widens the field of view, in radians
+  pub fn change_fov(&mut self, delta: f32) {
+    self.fov = (self.fov + delta).clamp(5.0 * PI / 180.0, 120.0 * PI / 180.0);
+    self.has_changed = true;
+  }
+
   pub fn check_if_changed(&mut self) -> bool {
     if self.has_changed {
       self.has_changed = false;
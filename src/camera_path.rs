@@ -0,0 +1,345 @@
+use crate::camera::Camera;
+use nalgebra_glm::Vec3;
+use std::fs;
+use std::io;
+use std::ops::{Add, Mul, Sub};
+
+// Seconds between keyframes recorded from the live window
+const KEYFRAME_SPACING: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    // Seconds from the start of the path
+    pub time: f32,
+    pub eye: Vec3,
+    pub center: Vec3,
+    pub up: Vec3,
+    // Vertical field of view in radians
+    pub fov: f32,
+}
+
+impl Keyframe {
+    pub fn from_camera(time: f32, camera: &Camera) -> Self {
+        Keyframe {
+            time,
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+            fov: camera.fov,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.eye = self.eye;
+        camera.center = self.center;
+        camera.up = self.up;
+        camera.fov = self.fov;
+        camera.has_changed = true;
+    }
+}
+
+// Keyframes sorted by time, interpolated with Catmull-Rom splines so the
+// camera passes through every keyframe without corners.
+//
+// Files have one keyframe per line, `#` starts a comment:
+//   time  eye.x eye.y eye.z  center.x center.y center.z  up.x up.y up.z  fov_degrees
+#[derive(Default)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?, path)
+    }
+
+    // Keyframes from the contents of a path file; `path` only labels errors
+    fn parse(contents: &str, path: &str) -> io::Result<Self> {
+        let mut keyframes = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<_, _>>()
+                .map_err(|error| invalid_line(path, number, &error.to_string()))?;
+            if values.len() != 11 {
+                return Err(invalid_line(path, number, "expected 11 numbers"));
+            }
+
+            keyframes.push(Keyframe {
+                time: values[0],
+                eye: Vec3::new(values[1], values[2], values[3]),
+                center: Vec3::new(values[4], values[5], values[6]),
+                up: Vec3::new(values[7], values[8], values[9]).normalize(),
+                fov: values[10].to_radians(),
+            });
+        }
+
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(CameraPath { keyframes })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, self.to_text())
+    }
+
+    // Contents of the file `save` writes
+    fn to_text(&self) -> String {
+        let mut contents = String::from(
+            "# time  eye.x eye.y eye.z  center.x center.y center.z  up.x up.y up.z  fov_degrees\n",
+        );
+        for k in &self.keyframes {
+            contents.push_str(&format!(
+                "{:.3}  {:.4} {:.4} {:.4}  {:.4} {:.4} {:.4}  {:.4} {:.4} {:.4}  {:.2}\n",
+                k.time,
+                k.eye.x,
+                k.eye.y,
+                k.eye.z,
+                k.center.x,
+                k.center.y,
+                k.center.z,
+                k.up.x,
+                k.up.y,
+                k.up.z,
+                k.fov.to_degrees(),
+            ));
+        }
+        contents
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    // Appends the camera a fixed spacing after the last keyframe; the file
+    // can be edited afterwards to adjust the timing
+    pub fn record(&mut self, camera: &Camera) {
+        let time = self
+            .keyframes
+            .last()
+            .map_or(0.0, |k| k.time + KEYFRAME_SPACING);
+        self.keyframes.push(Keyframe::from_camera(time, camera));
+    }
+
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(*first);
+        }
+        if time >= last.time {
+            return Some(*last);
+        }
+
+        let i = self
+            .keyframes
+            .windows(2)
+            .position(|pair| time < pair[1].time)
+            .unwrap_or(self.keyframes.len() - 2);
+
+        // The ends repeat their keyframe as the missing neighbour
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k1 = &self.keyframes[i];
+        let k2 = &self.keyframes[i + 1];
+        let k3 = &self.keyframes[(i + 2).min(self.keyframes.len() - 1)];
+
+        let span = k2.time - k1.time;
+        let u = if span > 0.0 { (time - k1.time) / span } else { 0.0 };
+
+        Some(Keyframe {
+            time,
+            eye: catmull_rom(k0.eye, k1.eye, k2.eye, k3.eye, u),
+            center: catmull_rom(k0.center, k1.center, k2.center, k3.center, u),
+            up: catmull_rom(k0.up, k1.up, k2.up, k3.up, u).normalize(),
+            fov: catmull_rom(k0.fov, k1.fov, k2.fov, k3.fov, u),
+        })
+    }
+}
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, u: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let u2 = u * u;
+    let u3 = u2 * u;
+
+    (p1 * 2.0
+        + (p2 - p0) * u
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * u2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * u3)
+        * 0.5
+}
+
+fn invalid_line(path: &str, number: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}: {}", path, number + 1, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "test.path";
+
+    fn keyframe(time: f32, eye: Vec3) -> Keyframe {
+        Keyframe {
+            time,
+            eye,
+            center: Vec3::zeros(),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            fov: 45f32.to_radians(),
+        }
+    }
+
+    fn path_of(keyframes: Vec<Keyframe>) -> CameraPath {
+        CameraPath { keyframes }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn parses_keyframes_sorted_by_time() {
+        let camera_path = CameraPath::parse(
+            "# comment line\n\
+             2.0  0 0 10  0 0 0  0 2 0  60 # trailing comment\n\
+             \n\
+             0.0  1 2 3  0 0 0  0 1 0  45\n",
+            PATH,
+        )
+        .unwrap();
+
+        assert_eq!(camera_path.keyframes.len(), 2);
+        assert_eq!(camera_path.keyframes[0].time, 0.0);
+        assert_eq!(camera_path.keyframes[0].eye, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(camera_path.keyframes[1].up, Vec3::new(0.0, 1.0, 0.0));
+        assert!((camera_path.keyframes[1].fov - 60f32.to_radians()).abs() < 1e-6);
+        assert_eq!(camera_path.duration(), 2.0);
+    }
+
+    #[test]
+    fn rejects_rows_with_the_wrong_number_of_fields() {
+        let result = CameraPath::parse("0.0  0 0 10  0 0 0  0 1 0  45\n1.0  0 0 10  0 0 0\n", PATH);
+        let error = result.err().unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "test.path:2: expected 11 numbers");
+    }
+
+    #[test]
+    fn rejects_rows_with_non_numbers() {
+        let error = CameraPath::parse("0.0  0 0 ten  0 0 0  0 1 0  45\n", PATH).err().unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("test.path:1: "), "{}", error);
+    }
+
+    #[test]
+    fn saved_text_parses_back() {
+        let mut saved = keyframe(0.5, Vec3::new(1.0, -2.0, 3.5));
+        saved.fov = 50f32.to_radians();
+        let camera_path = path_of(vec![saved]);
+
+        let parsed = CameraPath::parse(&camera_path.to_text(), PATH).unwrap();
+        assert_eq!(parsed.keyframes.len(), 1);
+        assert_eq!(parsed.keyframes[0].eye, saved.eye);
+        assert!((parsed.keyframes[0].fov - saved.fov).abs() < 1e-4);
+    }
+
+    #[test]
+    fn samples_pass_through_every_keyframe() {
+        let keyframes = vec![
+            keyframe(0.0, Vec3::new(0.0, 0.0, 10.0)),
+            keyframe(1.0, Vec3::new(5.0, 1.0, 8.0)),
+            keyframe(3.0, Vec3::new(9.0, -2.0, 0.0)),
+            keyframe(4.0, Vec3::new(4.0, 0.0, -6.0)),
+        ];
+        let camera_path = path_of(keyframes.clone());
+
+        for expected in &keyframes {
+            let sample = camera_path.sample(expected.time).unwrap();
+            assert_eq!(sample.time, expected.time);
+            assert_close(sample.eye, expected.eye);
+            assert_close(sample.up, expected.up);
+            assert!((sample.fov - expected.fov).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn samples_clamp_to_the_first_and_last_keyframe() {
+        let first = keyframe(1.0, Vec3::new(0.0, 0.0, 10.0));
+        let last = keyframe(2.0, Vec3::new(5.0, 0.0, 5.0));
+        let camera_path = path_of(vec![first, last]);
+
+        assert_close(camera_path.sample(-3.0).unwrap().eye, first.eye);
+        assert_close(camera_path.sample(0.5).unwrap().eye, first.eye);
+        assert_close(camera_path.sample(7.0).unwrap().eye, last.eye);
+    }
+
+    #[test]
+    fn samples_between_keyframes_stay_between_them() {
+        let camera_path = path_of(vec![
+            keyframe(0.0, Vec3::new(0.0, 0.0, 0.0)),
+            keyframe(2.0, Vec3::new(4.0, 0.0, 0.0)),
+        ]);
+
+        // With the ends repeated the spline is symmetric about the midpoint
+        assert_close(camera_path.sample(1.0).unwrap().eye, Vec3::new(2.0, 0.0, 0.0));
+        let quarter = camera_path.sample(0.5).unwrap().eye.x;
+        assert!(quarter > 0.0 && quarter < 2.0, "{}", quarter);
+    }
+
+    #[test]
+    fn samples_a_single_keyframe_at_any_time() {
+        let only = keyframe(2.0, Vec3::new(1.0, 2.0, 3.0));
+        let camera_path = path_of(vec![only]);
+
+        for time in [0.0, 2.0, 9.0] {
+            assert_close(camera_path.sample(time).unwrap().eye, only.eye);
+        }
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn samples_zero_length_spans_without_nans() {
+        let camera_path = path_of(vec![
+            keyframe(0.0, Vec3::new(0.0, 0.0, 10.0)),
+            keyframe(1.0, Vec3::new(1.0, 0.0, 10.0)),
+            keyframe(1.0, Vec3::new(2.0, 0.0, 10.0)),
+            keyframe(2.0, Vec3::new(3.0, 0.0, 10.0)),
+        ]);
+
+        for time in [0.5, 1.0, 1.5] {
+            let sample = camera_path.sample(time).unwrap();
+            assert!(sample.eye.iter().all(|value| value.is_finite()), "{:?}", sample.eye);
+            assert!(sample.fov.is_finite());
+        }
+    }
+
+    #[test]
+    fn records_keyframes_a_fixed_spacing_apart() {
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+        let mut camera_path = CameraPath::default();
+
+        camera_path.record(&camera);
+        camera_path.record(&camera);
+        camera_path.record(&camera);
+
+        let times: Vec<f32> = camera_path.keyframes.iter().map(|k| k.time).collect();
+        assert_eq!(times, vec![0.0, KEYFRAME_SPACING, 2.0 * KEYFRAME_SPACING]);
+        assert_close(camera_path.keyframes[2].eye, camera.eye);
+    }
+}
@@ -0,0 +1,263 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+// Tilt of the Earth's equator against the ecliptic (J2000)
+const OBLIQUITY_DEGREES: f32 = 23.439;
+
+pub struct CatalogStar {
+    // Hipparcos number, which constellation files use to refer to stars
+    pub hip: Option<u32>,
+    // Unit vector in the scene's frame
+    pub direction: Vec3,
+    pub magnitude: f32,
+    // B-V color index; blue stars are negative, red ones above 1
+    pub color_index: f32,
+}
+
+// Loads a star catalog CSV with a header row. Columns are found by name, as
+// in the HYG database: `ra` in hours, `dec` in degrees, `mag`, `ci` for B-V
+// and an optional `hip`. Stars fainter than `magnitude_limit` are skipped, and
+// so is the Sun, which HYG lists as its first row.
+pub fn load_catalog(path: &str, magnitude_limit: f32) -> io::Result<Vec<CatalogStar>> {
+    parse_catalog(&fs::read_to_string(path)?, path, magnitude_limit)
+}
+
+// The CSV itself, with `path` naming the source in errors
+fn parse_catalog(contents: &str, path: &str, magnitude_limit: f32) -> io::Result<Vec<CatalogStar>> {
+    let mut lines = contents.lines().enumerate();
+
+    let header: Vec<String> = match lines.next() {
+        Some((_, header)) => split_row(header).map(|name| name.to_lowercase()).collect(),
+        None => return Err(invalid_line(path, 0, "empty catalog")),
+    };
+    let column = |names: &[&str]| header.iter().position(|name| names.contains(&name.as_str()));
+    let (Some(ra), Some(dec), Some(mag)) = (
+        column(&["ra", "rahours"]),
+        column(&["dec", "decdeg"]),
+        column(&["mag", "vmag"]),
+    ) else {
+        return Err(invalid_line(path, 0, "expected ra, dec and mag columns"));
+    };
+    let ci = column(&["ci", "b-v", "bv"]);
+    let hip = column(&["hip"]);
+
+    let mut stars = Vec::new();
+    for (number, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = split_row(line).collect();
+        let number_at = |index: usize| fields.get(index).and_then(|field| field.parse::<f32>().ok());
+
+        let (Some(ra), Some(dec), Some(magnitude)) = (number_at(ra), number_at(dec), number_at(mag)) else {
+            return Err(invalid_line(path, number, "expected numbers for ra, dec and mag"));
+        };
+        if magnitude > magnitude_limit || magnitude < -5.0 {
+            continue;
+        }
+
+        stars.push(CatalogStar {
+            hip: hip.and_then(|index| fields.get(index)?.parse().ok()),
+            direction: equatorial_direction(ra, dec),
+            magnitude,
+            // Stars without a measured color are drawn white-ish, like the Sun
+            color_index: ci.and_then(number_at).unwrap_or(0.65),
+        });
+    }
+
+    Ok(stars)
+}
+
+// Loads constellation lines in Stellarium's constellationship.fab format: a
+// name, the number of segments and that many pairs of Hipparcos numbers
+pub fn load_constellation_lines(path: &str) -> io::Result<Vec<(u32, u32)>> {
+    parse_constellation_lines(&fs::read_to_string(path)?, path)
+}
+
+fn parse_constellation_lines(contents: &str, path: &str) -> io::Result<Vec<(u32, u32)>> {
+    let mut segments = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace().skip(1);
+        let count: usize = fields
+            .next()
+            .ok_or_else(|| invalid_line(path, number, "expected a segment count"))?
+            .parse()
+            .map_err(|error: std::num::ParseIntError| invalid_line(path, number, &error.to_string()))?;
+        let hips: Vec<u32> = fields
+            .map(str::parse::<u32>)
+            .collect::<Result<_, _>>()
+            .map_err(|error| invalid_line(path, number, &error.to_string()))?;
+        if hips.len() % 2 != 0 {
+            return Err(invalid_line(path, number, "expected pairs of star numbers"));
+        }
+        // A truncated line still has whole pairs, the count catches it
+        if hips.len() / 2 != count {
+            let message = format!("expected {} segments, found {}", count, hips.len() / 2);
+            return Err(invalid_line(path, number, &message));
+        }
+
+        segments.extend(hips.chunks(2).map(|pair| (pair[0], pair[1])));
+    }
+
+    Ok(segments)
+}
+
+// Constellation segments as index pairs into `stars`, dropping those whose
+// stars are not in the catalog
+pub fn resolve_lines(stars: &[CatalogStar], segments: &[(u32, u32)]) -> Vec<(usize, usize)> {
+    let by_hip: HashMap<u32, usize> = stars
+        .iter()
+        .enumerate()
+        .filter_map(|(index, star)| star.hip.map(|hip| (hip, index)))
+        .collect();
+
+    segments
+        .iter()
+        .filter_map(|(a, b)| Some((*by_hip.get(a)?, *by_hip.get(b)?)))
+        .collect()
+}
+
+// Direction of a right ascension (hours) and declination (degrees) in the
+// scene. The scene's XZ plane is the ecliptic with +Y towards the ecliptic
+// north pole, and +X towards the March equinox.
+pub fn equatorial_direction(ra_hours: f32, dec_degrees: f32) -> Vec3 {
+    let (ra, dec) = ((ra_hours * 15.0).to_radians(), dec_degrees.to_radians());
+    let equatorial = Vec3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin());
+
+    // Tilt the equator down onto the ecliptic around the equinox direction
+    let obliquity = OBLIQUITY_DEGREES.to_radians();
+    let ecliptic = Vec3::new(
+        equatorial.x,
+        equatorial.y * obliquity.cos() + equatorial.z * obliquity.sin(),
+        -equatorial.y * obliquity.sin() + equatorial.z * obliquity.cos(),
+    );
+
+    // Ecliptic x, y, z to scene X, -Z, Y keeps the frame right-handed
+    Vec3::new(ecliptic.x, ecliptic.z, -ecliptic.y)
+}
+
+fn split_row(line: &str) -> impl Iterator<Item = &str> {
+    line.split(',').map(|field| field.trim().trim_matches('"'))
+}
+
+fn invalid_line(path: &str, number: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}: {}", path, number + 1, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog(contents: &str) -> io::Result<Vec<CatalogStar>> {
+        parse_catalog(contents, "stars.csv", 6.5)
+    }
+
+    fn constellation_lines(contents: &str) -> io::Result<Vec<(u32, u32)>> {
+        parse_constellation_lines(contents, "lines.fab")
+    }
+
+    fn error_message(result: io::Result<impl Sized>) -> String {
+        let error = result.err().expect("expected an error");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        error.to_string()
+    }
+
+    #[test]
+    fn loads_stars_by_column_name() {
+        let stars = catalog(
+            "id,hip,proper,RA,Dec,Mag,CI\n\
+             0,,Sol,0,0,-26.7,0.656\n\
+             1,32349,Sirius,6.752481,-16.716116,-1.44,0.009\n\
+             2,,,1.0,2.0,7.5,\n\
+             3,91262,Vega,18.615649,38.783692,0.03,\n",
+        )
+        .unwrap();
+
+        // The Sun and the star past the magnitude limit are skipped
+        assert_eq!(stars.len(), 2);
+        assert_eq!(stars[0].hip, Some(32349));
+        assert_eq!(stars[0].magnitude, -1.44);
+        assert_eq!(stars[0].color_index, 0.009);
+        assert!((stars[0].direction.magnitude() - 1.0).abs() < 1e-5);
+        // No measured color falls back to a Sun-like index
+        assert_eq!(stars[1].color_index, 0.65);
+    }
+
+    #[test]
+    fn rejects_an_empty_catalog() {
+        assert!(error_message(catalog("")).ends_with(":1: empty catalog"));
+    }
+
+    #[test]
+    fn rejects_a_catalog_missing_columns() {
+        let message = error_message(catalog("hip,ra,mag\n1,2.0,3.0\n"));
+        assert!(message.ends_with(":1: expected ra, dec and mag columns"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_bad_rows() {
+        let message = error_message(catalog("ra,dec,mag\n1.0,2.0,3.0\n1.0,north,3.0\n"));
+        assert!(message.ends_with(":3: expected numbers for ra, dec and mag"), "{}", message);
+
+        let message = error_message(catalog("ra,dec,mag\n1.0,2.0\n"));
+        assert!(message.ends_with(":2: expected numbers for ra, dec and mag"), "{}", message);
+    }
+
+    #[test]
+    fn loads_constellation_lines() {
+        let segments = constellation_lines(
+            "# comment\n\
+             Ori 2 26727 26311 26311 25930\n\
+             \n\
+             UMi 1 11767 85822 # Polaris\n",
+        )
+        .unwrap();
+        assert_eq!(segments, vec![(26727, 26311), (26311, 25930), (11767, 85822)]);
+    }
+
+    #[test]
+    fn rejects_odd_star_numbers() {
+        let message = error_message(constellation_lines("Ori 2 26727 26311 26311\n"));
+        assert!(message.ends_with(":1: expected pairs of star numbers"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_lines_that_disagree_with_their_count() {
+        let contents = "UMi 1 11767 85822\nOri 3 26727 26311 26311 25930\n";
+        let message = error_message(constellation_lines(contents));
+        assert!(message.ends_with(":2: expected 3 segments, found 2"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_a_missing_or_bad_count() {
+        let message = error_message(constellation_lines("Ori\n"));
+        assert!(message.ends_with(":1: expected a segment count"), "{}", message);
+
+        let message = error_message(constellation_lines("Ori two 26727 26311\n"));
+        assert!(message.contains(":1: "), "{}", message);
+    }
+
+    #[test]
+    fn resolves_only_segments_with_both_stars() {
+        let star = |hip| CatalogStar {
+            hip,
+            direction: Vec3::new(1.0, 0.0, 0.0),
+            magnitude: 1.0,
+            color_index: 0.0,
+        };
+        let stars = [star(Some(10)), star(None), star(Some(30))];
+        let lines = resolve_lines(&stars, &[(10, 30), (30, 20), (30, 10)]);
+        assert_eq!(lines, vec![(0, 2), (2, 0)]);
+    }
+}
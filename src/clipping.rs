@@ -0,0 +1,78 @@
+use crate::projection::DepthRange;
+use crate::shaders::clip_to_screen;
+use crate::vertex::Vertex;
+use crate::Uniforms;
+use nalgebra_glm::Vec4;
+
+// Signed distances of a clip-space position to the near and far planes,
+// positive on the visible side. Reversed depth puts the near plane at
+// z / w = 1 and the far one at 0; the other modes use -1 and 1 (logarithmic
+// depth only changes what is written, not the projection).
+fn plane_distances(clip: &Vec4, depth: &DepthRange) -> [f32; 2] {
+    if depth.is_reversed() {
+        [clip.w - clip.z, clip.z]
+    } else {
+        [clip.w + clip.z, clip.w - clip.z]
+    }
+}
+
+// Whether the whole triangle lies between the near and far planes and can be
+// rasterized as it is
+pub fn is_inside_depth_range(vertices: [&Vertex; 3], depth: &DepthRange) -> bool {
+    vertices.iter().all(|vertex| {
+        plane_distances(&vertex.clip_position, depth)
+            .iter()
+            .all(|distance| *distance >= 0.0)
+    })
+}
+
+// Sutherland-Hodgman clipping of a triangle against the near and far planes,
+// in clip space where they are flat. Vertices behind the camera would divide
+// by a negative w and project to huge, inverted triangles, so the part past a
+// plane is cut off and what is left comes back as a fan of triangles.
+pub fn clip_triangle(vertices: [&Vertex; 3], uniforms: &Uniforms) -> Vec<[Vertex; 3]> {
+    let mut polygon: Vec<Vertex> = vertices.iter().map(|vertex| (*vertex).clone()).collect();
+
+    for plane in 0..2 {
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for (index, current) in polygon.iter().enumerate() {
+            let next = &polygon[(index + 1) % polygon.len()];
+            let d0 = plane_distances(&current.clip_position, &uniforms.depth)[plane];
+            let d1 = plane_distances(&next.clip_position, &uniforms.depth)[plane];
+
+            if d0 >= 0.0 {
+                clipped.push(current.clone());
+            }
+            if (d0 >= 0.0) != (d1 >= 0.0) {
+                clipped.push(interpolate(current, next, d0 / (d0 - d1), uniforms));
+            }
+        }
+
+        polygon = clipped;
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    (1..polygon.len() - 1)
+        .map(|index| [polygon[0].clone(), polygon[index].clone(), polygon[index + 1].clone()])
+        .collect()
+}
+
+// Vertex at `t` along the edge a-b. Every attribute is linear along the edge
+// in clip space; only the screen position has to be projected again.
+fn interpolate(a: &Vertex, b: &Vertex, t: f32, uniforms: &Uniforms) -> Vertex {
+    let clip_position = a.clip_position.lerp(&b.clip_position, t);
+
+    Vertex {
+        position: a.position.lerp(&b.position, t),
+        normal: a.normal.lerp(&b.normal, t),
+        tex_coords: a.tex_coords.lerp(&b.tex_coords, t),
+        tangent: a.tangent.lerp(&b.tangent, t),
+        color: a.color.lerp(&b.color, t),
+        clip_position,
+        transformed_position: clip_to_screen(&clip_position, uniforms),
+        transformed_normal: a.transformed_normal.lerp(&b.transformed_normal, t),
+        transformed_tangent: a.transformed_tangent.lerp(&b.transformed_tangent, t),
+    }
+}
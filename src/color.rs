@@ -1,3 +1,4 @@
+use nalgebra_glm::Vec3;
 use std::fmt;
 
 #[derive(Debug, Clone, Copy)]
@@ -9,7 +10,7 @@ pub struct Color {
 
 impl Color {
     // Constructor to initialize the color using r, g, b values as u8
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Color { r, g, b }
     }
 
@@ -40,6 +41,15 @@ impl Color {
         ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
 
+    // Function to return the color as floats in the 0.0 to 1.0 range
+    pub fn to_vec3(&self) -> Vec3 {
+        Vec3::new(
+            self.r as f32 / 255.0,
+            self.g as f32 / 255.0,
+            self.b as f32 / 255.0,
+        )
+    }
+
     // Linear interpolation between two colors
     pub fn lerp(&self, other: &Color, t: f32) -> Self {
         let t = t.clamp(0.0, 1.0);
@@ -0,0 +1,134 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::orbit::KeplerOrbit;
+use crate::particle::{time_steps, ParticleSettings, ParticleSystem};
+use crate::sphere::random_direction;
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+use rand::prelude::*;
+
+// Beyond this distance from the Sun the nucleus is frozen and has no tails
+const ACTIVE_DISTANCE: f32 = 30.0;
+// Longest step the tails are integrated with, in units of `Uniforms::time`;
+// fast-forwarded frames are split into several
+const MAX_STEP: f32 = 200.0;
+// How far back the tails are simulated the first time, so they are already
+// grown on the first frame
+const WARM_UP: f32 = 16000.0;
+
+// Particles per unit of time at perihelion
+const ION_RATE: f32 = 0.3;
+const DUST_RATE: f32 = 0.16;
+// Speed the solar wind carries ions away at, per unit of time
+const ION_SPEED: f32 = 1.4e-3;
+// Random speed dust leaves the nucleus with, relative to its orbital speed
+const DUST_SPREAD: f32 = 0.06;
+// Radiation pressure over gravity for dust grains, smallest to largest
+// effect; small grains are pushed harder and form the outer edge of the tail
+const DUST_BETA: (f32, f32) = (0.2, 0.9);
+
+const ION_TAIL: ParticleSettings = ParticleSettings {
+    lifetime: (3000.0, 5000.0),
+    size: (0.08, 0.3),
+    color: (Color::new(130, 190, 255), Color::new(40, 80, 220)),
+    brightness: 0.08,
+    max_particles: 3000,
+};
+
+const DUST_TAIL: ParticleSettings = ParticleSettings {
+    lifetime: (8000.0, 20000.0),
+    size: (0.1, 0.4),
+    color: (Color::new(255, 245, 215), Color::new(200, 150, 100)),
+    brightness: 0.06,
+    max_particles: 4000,
+};
+
+// Icy body on an eccentric orbit. Near the Sun it grows two tails: a straight
+// blue ion tail blown directly away from the Sun, and a broader, curved dust
+// tail whose grains keep the comet's orbital motion while sunlight pushes
+// them outwards.
+pub struct Comet {
+    orbit: KeplerOrbit,
+    sun: Vec3,
+    ion_tail: ParticleSystem,
+    dust_tail: ParticleSystem,
+    // Time the tails were last advanced to
+    simulated_until: Option<f32>,
+}
+
+impl Comet {
+    // The same seed always gives the same tails
+    pub fn new(sun: Vec3, orbit: KeplerOrbit, seed: u64) -> Self {
+        Comet {
+            orbit,
+            sun,
+            ion_tail: ParticleSystem::new(ION_TAIL, seed),
+            dust_tail: ParticleSystem::new(DUST_TAIL, seed.wrapping_add(1)),
+            simulated_until: None,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vec3 {
+        self.sun + self.orbit.position(time)
+    }
+
+    // Advances the tails to `time`. Going back in time leaves them as they are.
+    pub fn update(&mut self, time: f32) {
+        for (start, delta) in time_steps(self.simulated_until, time, WARM_UP, MAX_STEP) {
+            self.step(start, delta);
+        }
+        self.simulated_until = Some(self.simulated_until.map_or(time, |last| last.max(time)));
+    }
+
+    // How strongly the nucleus outgasses, 0 when frozen and 1 at perihelion
+    fn activity(&self, distance: f32) -> f32 {
+        let perihelion = self.orbit.semi_major_axis * (1.0 - self.orbit.eccentricity);
+        if distance >= ACTIVE_DISTANCE {
+            return 0.0;
+        }
+        // Sublimation follows the sunlight, which falls off with the square
+        // of the distance, eased out to nothing at the active distance
+        let sunlight = (perihelion / distance).powi(2);
+        let fade_out = (1.0 - distance / ACTIVE_DISTANCE) / (1.0 - perihelion / ACTIVE_DISTANCE);
+        (sunlight * fade_out).min(1.0)
+    }
+
+    fn step(&mut self, time: f32, delta: f32) {
+        let offset = self.orbit.position(time);
+        let nucleus = self.sun + offset;
+        let distance = offset.magnitude();
+        let away_from_sun = offset / distance;
+        let velocity = self.orbit.velocity(time);
+        let gravity = self.orbit.gravitational_parameter();
+        let activity = self.activity(distance);
+
+        // Ions stream straight out, faster the more active the comet is
+        self.ion_tail.update(delta, |_| Vec3::zeros());
+        let ion_speed = ION_SPEED * (0.5 + 0.5 * activity);
+        self.ion_tail.emit(ION_RATE * activity, delta, |rng, birth| {
+            let jitter = random_direction(rng) * 0.08;
+            (nucleus + velocity * birth, (away_from_sun + jitter) * ion_speed)
+        });
+
+        // Dust is lifted off the sunlit side by the gas and then follows its
+        // own orbit, under gravity weakened by radiation pressure
+        let sun = self.sun;
+        self.dust_tail.update(delta, |particle| {
+            let offset = particle.position - sun;
+            let distance = offset.magnitude().max(0.5);
+            let beta = DUST_BETA.0 + (DUST_BETA.1 - DUST_BETA.0) * particle.variation;
+            -offset / distance * (gravity * (1.0 - beta) / (distance * distance))
+        });
+        let dust_speed = velocity.magnitude() * DUST_SPREAD;
+        self.dust_tail.emit(DUST_RATE * activity, delta, |rng, birth| {
+            let drift = (random_direction(rng) - away_from_sun * 0.5) * dust_speed * rng.gen::<f32>();
+            (nucleus + velocity * birth, velocity + drift)
+        });
+    }
+
+    // `uniforms` should carry an identity model matrix
+    pub fn render_tails(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        self.dust_tail.render(framebuffer, uniforms);
+        self.ion_tail.render(framebuffer, uniforms);
+    }
+}
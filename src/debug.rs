@@ -0,0 +1,143 @@
+use crate::color::Color;
+use crate::fragment::Fragment;
+use crate::framebuffer::Framebuffer;
+use crate::line::{screen_line, LineStyle};
+use crate::projection::DepthRange;
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+
+const WIREFRAME_COLOR: Color = Color::new(80, 255, 120);
+// Pulls edges towards the camera so they win against their own triangles
+const WIREFRAME_BIAS: f32 = 1e-4;
+// Fragments per pixel that saturate the overdraw heatmap
+const OVERDRAW_MAX: f32 = 8.0;
+const HEATMAP: [Color; 5] = [
+    Color::new(0, 0, 0),
+    Color::new(30, 60, 200),
+    Color::new(40, 200, 80),
+    Color::new(250, 220, 40),
+    Color::new(240, 40, 30),
+];
+
+// What the pipeline shows in place of (or on top of) the planet shaders
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugView {
+    Shaded,
+    // Shaded image with the triangle edges on top
+    Wireframe,
+    // World-space normal as rgb
+    Normals,
+    // Grayscale depth buffer, white nearest
+    Depth,
+    // Fragments rasterized per pixel, hidden ones included
+    Overdraw,
+    // One flat, lightly shaded color per object
+    ObjectColors,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Shaded => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Normals,
+            DebugView::Normals => DebugView::Depth,
+            DebugView::Depth => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::ObjectColors,
+            DebugView::ObjectColors => DebugView::Shaded,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "Sombreado",
+            DebugView::Wireframe => "Alambre",
+            DebugView::Normals => "Normales",
+            DebugView::Depth => "Profundidad",
+            DebugView::Overdraw => "Sobredibujado",
+            DebugView::ObjectColors => "Objetos",
+        }
+    }
+
+    // Color to use instead of the object's shader, if this view replaces it
+    pub fn fragment_color(self, fragment: &Fragment, uniforms: &Uniforms) -> Option<Color> {
+        match self {
+            DebugView::Normals => {
+                let normal = fragment.normal * 0.5 + Vec3::new(0.5, 0.5, 0.5);
+                Some(Color::from_float(normal.x, normal.y, normal.z))
+            }
+            DebugView::ObjectColors => Some(object_color(uniforms) * (0.4 + 0.6 * fragment.intensity)),
+            _ => None,
+        }
+    }
+}
+
+// Stable color per object from `uniforms.object_id`. Hues step by the golden
+// ratio, so consecutive ids land far apart on the color wheel.
+fn object_color(uniforms: &Uniforms) -> Color {
+    let hue = (uniforms.object_id as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Color::from_float(r, g, b)
+}
+
+// Edges of the given screen-space triangles, depth tested with a small bias
+pub fn draw_wireframe(framebuffer: &mut Framebuffer, triangles: &[[Vec3; 3]], depth: &DepthRange) {
+    let bias = if depth.is_reversed() { WIREFRAME_BIAS } else { -WIREFRAME_BIAS };
+    let offset = Vec3::new(0.0, 0.0, bias);
+
+    for [a, b, c] in triangles {
+        let (a, b, c) = (a + offset, b + offset, c + offset);
+        for (start, end) in [(&a, &b), (&b, &c), (&c, &a)] {
+            screen_line(framebuffer, start, end, &WIREFRAME_COLOR, 0.8, LineStyle::Antialiased);
+        }
+    }
+}
+
+// Replaces the color buffer with the depth or overdraw view, once the frame's
+// geometry is done. Other views draw while rasterizing and do nothing here.
+pub fn resolve(framebuffer: &mut Framebuffer, view: DebugView, depth: &DepthRange) {
+    match view {
+        DebugView::Depth => resolve_depth(framebuffer, depth),
+        DebugView::Overdraw => resolve_overdraw(framebuffer),
+        _ => {}
+    }
+}
+
+// Stretches the depths in use this frame over the full gray range, so the
+// distribution of each depth mode is visible whatever the scene spans. Only
+// 0..=1 counts as geometry; pixels nothing covered keep the clear value, and
+// backdrops sit exactly on the far plane.
+fn resolve_depth(framebuffer: &mut Framebuffer, depth: &DepthRange) {
+    let is_geometry = |value: &f32| (0.0..=1.0).contains(value) && *value != depth.farthest();
+    let (min, max) = framebuffer
+        .zbuffer
+        .iter()
+        .filter(|value| is_geometry(value))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| (min.min(value), max.max(value)));
+    let span = (max - min).max(f32::EPSILON);
+
+    for (pixel, value) in framebuffer.buffer.iter_mut().zip(&framebuffer.zbuffer) {
+        if !is_geometry(value) {
+            *pixel = 0x000000;
+            continue;
+        }
+        let t = (value - min) / span;
+        let nearness = if depth.is_reversed() { t } else { 1.0 - t };
+        *pixel = Color::from_float(nearness, nearness, nearness).to_hex();
+    }
+}
+
+fn resolve_overdraw(framebuffer: &mut Framebuffer) {
+    for (pixel, &count) in framebuffer.buffer.iter_mut().zip(&framebuffer.overdraw) {
+        let t = (count as f32 / OVERDRAW_MAX).min(1.0) * (HEATMAP.len() - 1) as f32;
+        let index = (t as usize).min(HEATMAP.len() - 2);
+        *pixel = HEATMAP[index].lerp(&HEATMAP[index + 1], t - index as f32).to_hex();
+    }
+}
@@ -0,0 +1,81 @@
+use crate::camera::Camera;
+use nalgebra_glm::Vec3;
+
+// Camera distance from a focused body, in body radii
+const FRAMING_DISTANCE: f32 = 4.5;
+// Seconds the fly-to takes
+const TRANSITION_TIME: f32 = 2.0;
+
+// Keeps the camera on one body: first an eased flight from wherever the
+// camera was, then tracking that keeps the user's orbit offset as the body
+// moves.
+pub struct CameraFocus {
+    pub target: usize,
+    start_eye: Vec3,
+    start_center: Vec3,
+    // Direction from the body to the eye at the end of the flight
+    direction: Vec3,
+    distance: f32,
+    progress: f32,
+}
+
+impl CameraFocus {
+    pub fn new(target: usize, camera: &Camera, target_position: Vec3, target_radius: f32) -> Self {
+        // Arrive from the side the camera is already on
+        let direction = camera.eye - target_position;
+        let direction = if direction.magnitude() > 1e-4 {
+            direction.normalize()
+        } else {
+            Vec3::new(0.0, 0.0, 1.0)
+        };
+
+        CameraFocus {
+            target,
+            start_eye: camera.eye,
+            start_center: camera.center,
+            direction,
+            distance: target_radius * FRAMING_DISTANCE,
+            progress: 0.0,
+        }
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.progress < 1.0
+    }
+
+    // Moves where the flight started from along with the scene, when it is
+    // recentred
+    pub fn shift(&mut self, offset: Vec3) {
+        self.start_eye += offset;
+        self.start_center += offset;
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, target_position: Vec3, delta_time: f32) {
+        if self.is_transitioning() {
+            self.progress = (self.progress + delta_time / TRANSITION_TIME).min(1.0);
+            let t = ease_in_out(self.progress);
+
+            // The end point follows the body, so it is still framed on arrival.
+            // Measured back from it, so a body far smaller than the distance
+            // flown is not lost to rounding on arrival.
+            let end_eye = target_position + self.direction * self.distance;
+            camera.eye = end_eye + (self.start_eye - end_eye) * (1.0 - t);
+            camera.center = target_position + (self.start_center - target_position) * (1.0 - t);
+        } else {
+            // Orbit and zoom still work while tracking, they change this offset
+            let offset = camera.eye - camera.center;
+            camera.center = target_position;
+            camera.eye = target_position + offset;
+        }
+        camera.has_changed = true;
+    }
+}
+
+// Cubic ease-in-out on 0..=1
+fn ease_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
@@ -1,4 +1,4 @@
-use nalgebra_glm::{Vec3, Vec2};
+use nalgebra_glm::{Vec3, Vec2, Vec4};
 use crate::color::Color;
 
 pub struct Fragment {
@@ -6,28 +6,9 @@ pub struct Fragment {
     pub color: Color,
     pub depth: f32,
     pub normal: Vec3,
+    pub tangent: Vec4,
     pub intensity: f32,
     pub vertex_position: Vec3,
+    pub tex_coords: Vec2,
+    pub uv_footprint: f32,
 }
-
-impl Fragment {
-    pub fn new(
-        position: Vec2,
-        color: Color,
-        depth: f32,
-        normal: Vec3,
-        intensity: f32,
-        vertex_position: Vec3,
-    ) -> Self {  
-        Fragment {
-            position,
-            color,
-            depth,
-            normal,
-            intensity,
-            vertex_position,
-        }
-    }
-}
-
-
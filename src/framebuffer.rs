@@ -1,12 +1,18 @@
 // framebuffer.rs
 
+use crate::color::Color;
+
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
     pub zbuffer: Vec<f32>,
+    // Fragments rasterized per pixel this frame, for the overdraw debug view
+    pub overdraw: Vec<u32>,
     background_color: u32,
     current_color: u32,
+    // With reversed depth larger values are closer
+    reversed_depth: bool,
 }
 
 impl Framebuffer {
@@ -16,8 +22,10 @@ impl Framebuffer {
             height,
             buffer: vec![0; width * height],
             zbuffer: vec![f32::INFINITY; width * height],
+            overdraw: vec![0; width * height],
             background_color: 0x000000,
             current_color: 0xFFFFFF,
+            reversed_depth: false,
         }
     }
 
@@ -25,15 +33,28 @@ impl Framebuffer {
         for pixel in self.buffer.iter_mut() {
             *pixel = self.background_color;
         }
+        let farthest = if self.reversed_depth {
+            f32::NEG_INFINITY
+        } else {
+            f32::INFINITY
+        };
         for depth in self.zbuffer.iter_mut() {
-            *depth = f32::INFINITY;
+            *depth = farthest;
+        }
+        for count in self.overdraw.iter_mut() {
+            *count = 0;
         }
     }
 
     pub fn point(&mut self, x: usize, y: usize, depth: f32) {
         if x < self.width && y < self.height {
             let index = y * self.width + x;
-            if self.zbuffer[index] > depth {
+            let closer = if self.reversed_depth {
+                depth > self.zbuffer[index]
+            } else {
+                depth < self.zbuffer[index]
+            };
+            if closer {
                 self.buffer[index] = self.current_color;
                 self.zbuffer[index] = depth;
             }
@@ -44,7 +65,72 @@ impl Framebuffer {
         self.background_color = color;
     }
 
+    // Whether `depth` is in front of what is already stored at the pixel
+    pub fn depth_test(&self, x: usize, y: usize, depth: f32) -> bool {
+        let stored = self.zbuffer[y * self.width + x];
+        if self.reversed_depth {
+            depth > stored
+        } else {
+            depth < stored
+        }
+    }
+
+    // Depth-tested blend of `color` over the pixel by `alpha`. The depth buffer
+    // is left alone, so thin translucent primitives do not hide what follows.
+    pub fn blend_point(&mut self, x: i32, y: i32, depth: f32, color: &Color, alpha: f32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        if !self.depth_test(x, y, depth) {
+            return;
+        }
+
+        let index = y * self.width + x;
+        let background = Color::from_hex(self.buffer[index]);
+        self.buffer[index] = background.lerp(color, alpha.clamp(0.0, 1.0)).to_hex();
+    }
+
+    // Depth-tested additive blend, saturating. Like `blend_point` the depth
+    // buffer is left alone, so glowing particles pile up in any order.
+    pub fn add_point(&mut self, x: i32, y: i32, depth: f32, color: &Color) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        if self.depth_test(x as usize, y as usize, depth) {
+            self.add_pixel(x, y, color);
+        }
+    }
+
+    // Adds `color` onto the pixel, saturating, without a depth test
+    pub fn add_pixel(&mut self, x: i32, y: i32, color: &Color) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            let index = y as usize * self.width + x as usize;
+            self.buffer[index] = (Color::from_hex(self.buffer[index]) + *color).to_hex();
+        }
+    }
+
+    // Writes a pixel without touching the depth buffer, for overlays
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.buffer[y as usize * self.width + x as usize] = color;
+        }
+    }
+
+    // Takes effect on the next clear
+    pub fn set_reversed_depth(&mut self, reversed: bool) {
+        self.reversed_depth = reversed;
+    }
+
     pub fn set_current_color(&mut self, color: u32) {
         self.current_color = color;
     }
+
+    pub fn save(&self, path: &str) -> Result<(), image::ImageError> {
+        let mut image = image::RgbImage::new(self.width as u32, self.height as u32);
+        for (pixel, &color) in image.pixels_mut().zip(&self.buffer) {
+            *pixel = image::Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+        }
+        image.save(path)
+    }
 }
@@ -0,0 +1,158 @@
+use crate::bodies::{BodyData, BODIES};
+use crate::framebuffer::Framebuffer;
+use crate::text::{draw_text_shadowed, LINE_HEIGHT};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+const HUD_COLOR: u32 = 0xE0E0E0;
+const SELECTED_COLOR: u32 = 0xFFD060;
+const LABEL_COLOR: u32 = 0xA0C8FF;
+const DEBUG_COLOR: u32 = 0x80FF90;
+const MARGIN: i32 = 8;
+
+// What the HUD shows for one frame
+pub struct HudInfo<'a> {
+    pub sim_time: f32,
+    pub time_warp: f32,
+    pub camera_position: Vec3,
+    pub camera_mode: &'a str,
+    pub scale_mode: &'a str,
+    // Depth mode in use, which may differ from the one picked with L
+    pub depth_mode: &'a str,
+    // Baked, procedural or hidden
+    pub background: &'a str,
+    pub selected: Option<Selection<'a>>,
+    // Name of the debug view, when one is active
+    pub debug_view: Option<&'a str>,
+}
+
+// The selected body, in scene units
+pub struct Selection<'a> {
+    pub name: &'a str,
+    pub radius: f32,
+    // From the camera to the body's surface
+    pub distance: f32,
+    // Real measurements, for the bodies of `BODIES`
+    pub data: Option<&'a BodyData>,
+}
+
+pub struct Hud {
+    pub visible: bool,
+    pub labels: bool,
+    // Smoothed seconds per frame, so the FPS readout does not flicker
+    frame_time: f32,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Hud {
+            visible: true,
+            labels: true,
+            frame_time: 0.0,
+        }
+    }
+}
+
+impl Hud {
+    pub fn update(&mut self, delta_time: f32) {
+        self.frame_time = if self.frame_time == 0.0 {
+            delta_time
+        } else {
+            self.frame_time + (delta_time - self.frame_time) * 0.1
+        };
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, info: &HudInfo) {
+        if !self.visible {
+            return;
+        }
+
+        let fps = if self.frame_time > 0.0 { 1.0 / self.frame_time } else { 0.0 };
+        let position = info.camera_position;
+        let lines = [
+            format!("FPS {:.0}  ({:.1} ms)", fps, self.frame_time * 1000.0),
+            format!("Tiempo {:.0}  x{}", info.sim_time, info.time_warp),
+            format!("Cámara {}  ({:.1}, {:.1}, {:.1})", info.camera_mode, position.x, position.y, position.z),
+            format!("Escala {}", info.scale_mode),
+            format!("Profundidad {}", info.depth_mode),
+            format!("Fondo {}", info.background),
+        ];
+
+        let mut y = MARGIN;
+        for line in &lines {
+            draw_text_shadowed(framebuffer, MARGIN, y, line, HUD_COLOR, 1);
+            y += LINE_HEIGHT as i32;
+        }
+        if let Some(debug_view) = info.debug_view {
+            let text = format!("Vista {}", debug_view);
+            draw_text_shadowed(framebuffer, MARGIN, y, &text, DEBUG_COLOR, 1);
+            y += LINE_HEIGHT as i32;
+        }
+
+        if let Some(selected) = &info.selected {
+            let text = format!("Selección: {}", selected.name);
+            draw_text_shadowed(framebuffer, MARGIN, y + 4, &text, SELECTED_COLOR, 2);
+            y += 4 + 2 * LINE_HEIGHT as i32;
+
+            let mut lines = vec![format!(
+                "Radio {:.2}, a {:.2} de la cámara",
+                selected.radius, selected.distance
+            )];
+            if let Some(data) = selected.data {
+                lines.push(format!("Radio real {:.1} km, masa {:.3e} kg", data.radius, data.mass));
+                lines.push(format!(
+                    "Rotación {:.2} h, inclinación del eje {:.2}°",
+                    data.rotation_period, data.axial_tilt
+                ));
+                if let Some(orbit) = &data.orbit {
+                    lines.push(format!(
+                        "Órbita alrededor de {}: a {:.0} km, e {:.4}",
+                        BODIES[orbit.parent].name, orbit.semi_major_axis, orbit.eccentricity
+                    ));
+                    lines.push(format!("i {:.2}°, periodo {:.2} días", orbit.inclination, orbit.period));
+                }
+            }
+            for line in &lines {
+                draw_text_shadowed(framebuffer, MARGIN, y, line, SELECTED_COLOR, 1);
+                y += LINE_HEIGHT as i32;
+            }
+        }
+    }
+
+    // Name of each body next to it on screen. `bodies` holds name, center and
+    // radius in world space.
+    pub fn draw_labels(
+        &self,
+        framebuffer: &mut Framebuffer,
+        bodies: &[(&str, Vec3, f32)],
+        view: &Mat4,
+        projection: &Mat4,
+        viewport: &Mat4,
+    ) {
+        if !self.visible || !self.labels {
+            return;
+        }
+
+        let to_screen = |point: Vec3| {
+            let clip = projection * view * Vec4::new(point.x, point.y, point.z, 1.0);
+            if clip.w <= 0.0 {
+                return None;
+            }
+            let screen = viewport * Vec4::new(clip.x / clip.w, clip.y / clip.w, 0.0, 1.0);
+            Some((screen.x, screen.y))
+        };
+
+        // Screen-space up, to measure how big each body looks
+        let up = Vec3::new(view[(1, 0)], view[(1, 1)], view[(1, 2)]);
+
+        for &(name, center, radius) in bodies {
+            let (Some((x, y)), Some((_, top))) = (to_screen(center), to_screen(center + up * radius)) else {
+                continue;
+            };
+            let screen_radius = (y - top).abs();
+
+            let label_x = (x + screen_radius + 4.0) as i32;
+            let label_y = (y - 3.0) as i32;
+            draw_text_shadowed(framebuffer, label_x, label_y, name, LABEL_COLOR, 1);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineStyle {
+    // Bresenham, one full pixel per step
+    Aliased,
+    // Xiaolin Wu, coverage split between the two pixels nearest the line
+    Antialiased,
+}
+
+// Screen position and buffer depth of a line end
+#[derive(Clone, Copy)]
+struct ScreenPoint {
+    x: f32,
+    y: f32,
+    depth: f32,
+}
+
+impl ScreenPoint {
+    fn lerp(&self, other: &ScreenPoint, t: f32) -> ScreenPoint {
+        ScreenPoint {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+            depth: self.depth + (other.depth - self.depth) * t,
+        }
+    }
+}
+
+// Draws the model-space segment a-b, depth tested against what is already in
+// the framebuffer and blended by `alpha`. Lines do not write depth.
+pub fn line(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    a: &Vec3,
+    b: &Vec3,
+    color: &Color,
+    alpha: f32,
+    style: LineStyle,
+) {
+    let to_view = |p: &Vec3| uniforms.view_matrix * uniforms.model_matrix * Vec4::new(p.x, p.y, p.z, 1.0);
+    let (mut view_a, mut view_b) = (to_view(a), to_view(b));
+
+    // Near-plane clipping in view space, where the camera looks down -Z. The
+    // plane is nudged forward so clipped ends stay inside the depth range.
+    let near = -uniforms.depth.near * 1.001;
+    let (a_behind, b_behind) = (view_a.z > near, view_b.z > near);
+    if a_behind && b_behind {
+        return;
+    }
+    if a_behind || b_behind {
+        let t = (near - view_a.z) / (view_b.z - view_a.z);
+        let clipped = view_a + (view_b - view_a) * t;
+        if a_behind {
+            view_a = clipped;
+        } else {
+            view_b = clipped;
+        }
+    }
+
+    let (Some(a), Some(b)) = (project(&view_a, uniforms), project(&view_b, uniforms)) else {
+        return;
+    };
+    draw(framebuffer, a, b, color, alpha, style);
+}
+
+// Same as `line` for a segment already in screen space: x and y in pixels and
+// z the buffer depth, as in `Vertex::transformed_position`
+pub fn screen_line(
+    framebuffer: &mut Framebuffer,
+    a: &Vec3,
+    b: &Vec3,
+    color: &Color,
+    alpha: f32,
+    style: LineStyle,
+) {
+    let a = ScreenPoint { x: a.x, y: a.y, depth: a.z };
+    let b = ScreenPoint { x: b.x, y: b.y, depth: b.z };
+    draw(framebuffer, a, b, color, alpha, style);
+}
+
+fn draw(
+    framebuffer: &mut Framebuffer,
+    a: ScreenPoint,
+    b: ScreenPoint,
+    color: &Color,
+    alpha: f32,
+    style: LineStyle,
+) {
+    let Some((a, b)) = clip_to_screen(a, b, framebuffer.width as f32, framebuffer.height as f32) else {
+        return;
+    };
+
+    match style {
+        LineStyle::Aliased => bresenham(framebuffer, a, b, color, alpha),
+        LineStyle::Antialiased => xiaolin_wu(framebuffer, a, b, color, alpha),
+    }
+}
+
+fn project(view_point: &Vec4, uniforms: &Uniforms) -> Option<ScreenPoint> {
+    let clip = uniforms.projection_matrix * view_point;
+    if clip.w <= 0.0 {
+        return None;
+    }
+
+    let depth = uniforms.depth.depth(clip.z / clip.w, clip.w);
+    let screen = uniforms.viewport_matrix * Vec4::new(clip.x / clip.w, clip.y / clip.w, 0.0, 1.0);
+
+    Some(ScreenPoint {
+        x: screen.x,
+        y: screen.y,
+        depth,
+    })
+}
+
+// Liang-Barsky clipping to the pixel rectangle, so lines that run far off
+// screen do not step through millions of invisible pixels
+fn clip_to_screen(a: ScreenPoint, b: ScreenPoint, width: f32, height: f32) -> Option<(ScreenPoint, ScreenPoint)> {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+
+    for (p, q) in [
+        (-dx, a.x),
+        (dx, width - 1.0 - a.x),
+        (-dy, a.y),
+        (dy, height - 1.0 - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = q / p;
+        if p < 0.0 {
+            if t > t1 {
+                return None;
+            }
+            t0 = t0.max(t);
+        } else {
+            if t < t0 {
+                return None;
+            }
+            t1 = t1.min(t);
+        }
+    }
+
+    Some((a.lerp(&b, t0), a.lerp(&b, t1)))
+}
+
+fn plot(framebuffer: &mut Framebuffer, x: i32, y: i32, depth: f32, color: &Color, alpha: f32) {
+    if alpha > 0.0 && (0.0..=1.0).contains(&depth) {
+        framebuffer.blend_point(x, y, depth, color, alpha);
+    }
+}
+
+fn bresenham(framebuffer: &mut Framebuffer, a: ScreenPoint, b: ScreenPoint, color: &Color, alpha: f32) {
+    let (mut x, mut y) = (a.x as i32, a.y as i32);
+    let (x1, y1) = (b.x as i32, b.y as i32);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let steps = dx.max(-dy).max(1) as f32;
+    let mut step = 0.0;
+
+    loop {
+        let depth = a.depth + (b.depth - a.depth) * (step / steps).min(1.0);
+        plot(framebuffer, x, y, depth, color, alpha);
+        if x == x1 && y == y1 {
+            break;
+        }
+
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+        step += 1.0;
+    }
+}
+
+fn xiaolin_wu(framebuffer: &mut Framebuffer, a: ScreenPoint, b: ScreenPoint, color: &Color, alpha: f32) {
+    // Pixel centers sit at +0.5, the algorithm wants them on integers
+    let mut a = ScreenPoint { x: a.x - 0.5, y: a.y - 0.5, ..a };
+    let mut b = ScreenPoint { x: b.x - 0.5, y: b.y - 0.5, ..b };
+
+    // Step along the major axis, left to right
+    let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+    if steep {
+        std::mem::swap(&mut a.x, &mut a.y);
+        std::mem::swap(&mut b.x, &mut b.y);
+    }
+    if a.x > b.x {
+        std::mem::swap(&mut a, &mut b);
+    }
+
+    let dx = b.x - a.x;
+    let gradient = if dx == 0.0 { 1.0 } else { (b.y - a.y) / dx };
+
+    let mut plot_coverage = |x: i32, y: i32, t: f32, coverage: f32| {
+        let depth = a.depth + (b.depth - a.depth) * t;
+        let (px, py) = if steep { (y, x) } else { (x, y) };
+        plot(framebuffer, px, py, depth, color, alpha * coverage);
+    };
+
+    // First end
+    let x_start = a.x.round();
+    let y_start = a.y + gradient * (x_start - a.x);
+    let gap = 1.0 - fract(a.x + 0.5);
+    let x1 = x_start as i32;
+    plot_coverage(x1, y_start.floor() as i32, 0.0, (1.0 - fract(y_start)) * gap);
+    plot_coverage(x1, y_start.floor() as i32 + 1, 0.0, fract(y_start) * gap);
+
+    // Second end
+    let x_end = b.x.round();
+    let y_end = b.y + gradient * (x_end - b.x);
+    let gap = fract(b.x + 0.5);
+    let x2 = x_end as i32;
+    plot_coverage(x2, y_end.floor() as i32, 1.0, (1.0 - fract(y_end)) * gap);
+    plot_coverage(x2, y_end.floor() as i32 + 1, 1.0, fract(y_end) * gap);
+
+    // Everything in between
+    let span = (x2 - x1).max(1) as f32;
+    let mut y = y_start + gradient;
+    for x in (x1 + 1)..x2 {
+        let t = (x - x1) as f32 / span;
+        plot_coverage(x, y.floor() as i32, t, 1.0 - fract(y));
+        plot_coverage(x, y.floor() as i32 + 1, t, fract(y));
+        y += gradient;
+    }
+}
+
+fn fract(value: f32) -> f32 {
+    value - value.floor()
+}
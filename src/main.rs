@@ -1,34 +1,93 @@
-use minifb::{Key, Window, WindowOptions};
-use nalgebra_glm::{look_at, perspective, Mat4, Vec3};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use nalgebra_glm::{look_at, Mat4, Vec3};
 use std::f32::consts::PI;
+use std::fs;
+use std::io;
 use std::time::Instant;
 
+mod asteroid;
+mod background;
+mod bake;
+mod bodies;
+mod bump;
 mod camera;
+mod camera_path;
+mod catalog;
+mod clipping;
 mod color;
+mod comet;
+mod debug;
+mod focus;
 mod fragment;
 mod framebuffer;
+mod hud;
+mod line;
+mod material;
+mod mesh;
 mod obj;
+mod orbit;
+mod particle;
+mod picking;
 mod planet;
+mod projection;
+mod rings;
 mod shaders;
+mod ship;
 mod skybox;
+mod solar_activity;
+mod star;
+mod sphere;
+mod text;
+mod texture;
 mod triangle;
 mod vertex;
 
+use asteroid::{AsteroidBelt, BELT_AU};
+use background::Background;
+use bake::{
+    bake, cube_normals_to_equirectangular, cube_to_equirectangular, BakeLayout, BakeSettings, HeightFn,
+    SurfaceFn,
+};
+use bodies::{body_index, is_primary, Layout, ScaleMode, BODIES, BODY_COUNT, SUN};
+use bump::{Displacement, HeightSource};
 use camera::Camera;
+use camera_path::CameraPath;
+use catalog::{load_catalog, load_constellation_lines, resolve_lines};
+use clipping::{clip_triangle, is_inside_depth_range};
 use color::Color;
+use comet::Comet;
+use debug::DebugView;
+use focus::CameraFocus;
 use fastnoise_lite::{CellularDistanceFunction, FastNoiseLite, FractalType, NoiseType};
 use fragment::Fragment;
 use framebuffer::Framebuffer;
+use hud::{Hud, HudInfo, Selection};
+use line::LineStyle;
+use material::Material;
+use mesh::{Instance, Mesh, VertexCache};
 use obj::Obj;
+use orbit::{draw_orbit, KeplerOrbit, Orbit};
+use picking::{pick, Ray};
+use projection::{DepthRange, Projection};
+use rings::{RingSystem, SATURN_RINGS};
 use shaders::{
-    fragment_shader, shader_earth, shader_eris, shader_jupiter, shader_mars, shader_mercury,
-    shader_moon, shader_neptune, shader_phobos, shader_pluto, shader_ring, shader_saturn,
-    shader_sedna, shader_uranus, shader_uranus_ring, shader_venus, vertex_shader,
+    fragment_shader, height_earth, height_mars, height_mercury, height_moon, height_phobos,
+    shader_asteroid, shader_earth, shader_earth_textured, shader_eris, shader_jupiter, shader_mars,
+    shader_material, shader_mercury, shader_moon, shader_neptune, shader_phobos, shader_pluto, shader_ring,
+    shader_saturn, shader_sedna, shader_textured, shader_uranus, shader_uranus_ring,
+    shader_venus, surface_earth, surface_eris, surface_jupiter, surface_mars, surface_mercury,
+    surface_moon, surface_neptune, surface_phobos, surface_pluto, surface_saturn, surface_sedna,
+    surface_uranus, surface_venus, vertex_shader,
 };
-use skybox::Skybox;
+use ship::{Ship, ShipControls};
+use skybox::{sky_matrices, Backdrop, Skybox};
+use solar_activity::SolarActivity;
+use sphere::{SphereKind, SphereLod, SPHERE_RADIUS};
+use texture::{CubeMap, FilterMode, Texture};
 use triangle::triangle;
 use vertex::Vertex;
 
+#[derive(Clone)]
 pub struct Uniforms<'a> {
     pub model_matrix: Mat4,
     pub view_matrix: Mat4,
@@ -36,12 +95,232 @@ pub struct Uniforms<'a> {
     pub viewport_matrix: Mat4,
     pub time: f32,
     pub noises: Vec<&'a FastNoiseLite>,
+    pub textures: Vec<&'a Texture>,
+    pub material: Option<&'a Material>,
+    pub displacement: Option<Displacement<'a>>,
+    pub depth: DepthRange,
+    pub debug: DebugView,
+    // Identificador estable del objeto dibujado, para la vista de colores por objeto
+    pub object_id: u32,
+}
+
+// Quién controla la cámara: la órbita de handle_input, el vuelo libre, la nave,
+// la vista cenital ortográfica del planetario o un recorrido grabado
+#[derive(Clone, Copy, PartialEq)]
+enum CameraMode {
+    Orbit,
+    FreeFly,
+    Chase,
+    Orrery,
+    Path,
+}
+
+impl CameraMode {
+    fn name(self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "órbita",
+            CameraMode::FreeFly => "vuelo libre",
+            CameraMode::Chase => "persecución",
+            CameraMode::Orrery => "planetario",
+            CameraMode::Path => "recorrido",
+        }
+    }
+}
+
+// Altura del ojo sobre el centro en la vista del planetario
+const ORRERY_HEIGHT: f32 = 100.0;
+
+// Renderizado sin ventana de un recorrido de cámara, un PNG por frame
+struct HeadlessRender {
+    out_dir: String,
+    fps: f32,
+    frame: usize,
+}
+
+// Recorrido por defecto, donde K va guardando los keyframes
+const RECORDED_PATH: &str = "assets/paths/recorded.path";
+
+// Semilla del cielo de estrellas, el mismo en cada ejecución, y cuántas tiene
+// el cielo generado
+const SKYBOX_SEED: u64 = 1977;
+const SKYBOX_STAR_COUNT: usize = 5000;
+// Fondo pintado opcional: seis caras skybox/space_<px|nx|py|ny|pz|nz>.png o un
+// panorama equirectangular skybox/space.png (o .jpg)
+const BACKDROP_DIR: &str = "assets/skybox";
+const BACKDROP_NAME: &str = "space";
+// Resolución de cada cara del cube map del fondo
+const BACKGROUND_CUBE_SIZE: usize = 256;
+// Catálogo de estrellas (CSV como el de HYG) y líneas de constelaciones
+const STAR_CATALOG: &str = "assets/stars/catalog.csv";
+const CONSTELLATION_LINES: &str = "assets/stars/constellations.fab";
+// Magnitud más débil que se carga del catálogo, el límite a simple vista
+const CATALOG_MAGNITUDE_LIMIT: f32 = 6.5;
+// Un catálogo con menos estrellas solo trae las brillantes (como la selección
+// incluida) y el resto del cielo se completa con estrellas generadas
+const COMPLETE_CATALOG_STARS: usize = 1000;
+
+// Cielo real desde el catálogo si existe; si no, uno aleatorio con semilla fija
+fn load_skybox() -> Skybox {
+    let catalog = match load_catalog(STAR_CATALOG, CATALOG_MAGNITUDE_LIMIT) {
+        Ok(catalog) if !catalog.is_empty() => catalog,
+        Ok(_) => return Skybox::new(SKYBOX_STAR_COUNT, SKYBOX_SEED),
+        Err(error) => {
+            if error.kind() != io::ErrorKind::NotFound {
+                eprintln!("{}: {}", STAR_CATALOG, error);
+            }
+            return Skybox::new(SKYBOX_STAR_COUNT, SKYBOX_SEED);
+        }
+    };
+
+    let lines = match load_constellation_lines(CONSTELLATION_LINES) {
+        Ok(segments) => resolve_lines(&catalog, &segments),
+        Err(error) => {
+            if error.kind() != io::ErrorKind::NotFound {
+                eprintln!("{}: {}", CONSTELLATION_LINES, error);
+            }
+            Vec::new()
+        }
+    };
+
+    let skybox = Skybox::from_catalog(&catalog, lines);
+    if catalog.len() >= COMPLETE_CATALOG_STARS {
+        return skybox;
+    }
+    // Las generadas son todas más débiles que la más débil del catálogo
+    let faintest = catalog
+        .iter()
+        .map(|star| star.magnitude)
+        .fold(f32::NEG_INFINITY, f32::max);
+    skybox.with_generated_stars(SKYBOX_STAR_COUNT, SKYBOX_SEED, faintest)
+}
+
+// Distancia de la cámara a la que las órbitas terminan de desvanecerse, en la
+// escala visual y en las escalas real y logarítmica, que ocupan más
+const ORBIT_FADE_DISTANCE: f32 = 80.0;
+const REAL_ORBIT_FADE_DISTANCE: f32 = 400.0;
+
+// Límites del plano cercano, que se acerca a la cámara junto a cuerpos
+// pequeños como los de la escala real
+const MIN_NEAR_PLANE: f32 = 1e-7;
+const MAX_NEAR_PLANE: f32 = 0.1;
+
+// Cinturón de asteroides entre Marte y Júpiter, siempre el mismo
+const ASTEROID_COUNT: usize = 2500;
+const ASTEROID_SEED: u64 = 433;
+// Radios del borde interior y exterior, medidos desde el Sol, en la escala visual
+const ASTEROID_BELT_RADII: (f32, f32) = (14.8, 17.2);
+
+// Cometa: perihelio justo fuera de Venus, del lado de la cámara inicial, y
+// afelio más allá de Sedna. Empieza poco antes del perihelio, con las colas ya
+// crecidas.
+const COMET_ORBIT: KeplerOrbit = KeplerOrbit {
+    semi_major_axis: 22.0,
+    eccentricity: 0.73,
+    inclination: 0.35,
+    ascending_node: 0.0,
+    periapsis_argument: -PI / 2.0,
+    mean_anomaly: -0.1,
+    mean_motion: 2.1e-5,
+};
+const COMET_SEED: u64 = 1986;
+const SOLAR_ACTIVITY_SEED: u64 = 1859;
+
+// Anillos de Saturno, siempre los mismos
+const SATURN_RINGS_SEED: i32 = 1610;
+
+// Identificadores de objeto para la vista de colores por objeto: los cuerpos
+// usan su índice en `BODIES` y lo demás sigue después, con los asteroides al
+// final, uno por roca
+const COMET_ID: u32 = BODY_COUNT as u32;
+const MOON_RING_IDS: [u32; 2] = [COMET_ID + 1, COMET_ID + 2];
+const URANUS_RING_ID: u32 = COMET_ID + 3;
+const SATURN_RINGS_ID: u32 = COMET_ID + 4;
+const SHIP_ID: u32 = COMET_ID + 5;
+const FIRST_ASTEROID_ID: u32 = COMET_ID + 6;
+
+// Cuerpo padre (índice de `BODIES`) y eje de la órbita de cada cuerpo en la
+// escala visual. Todos giran en el plano XZ salvo Fobos, que gira en el plano
+// XY. El Sol y el cometa, que no sigue un círculo, no tienen órbita dibujada.
+fn body_orbit(index: usize) -> Option<(usize, Vec3)> {
+    let body = BODIES.get(index)?;
+    let axis = if body.name == "Fobos" {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(0.0, -1.0, 0.0)
+    };
+    Some((body.parent()?, axis))
+}
+
+// Cinturón de asteroides para cada escala. Fuera de la escala visual sigue su
+// lugar real entre Marte y Júpiter, con rocas de su tamaño real en esa escala
+// y órbitas al ritmo de las de los planetas.
+fn create_asteroid_belt(mode: ScaleMode, sun: Vec3) -> AsteroidBelt {
+    if mode == ScaleMode::Visual {
+        return AsteroidBelt::new(sun, ASTEROID_BELT_RADII.0, ASTEROID_BELT_RADII.1, ASTEROID_COUNT, ASTEROID_SEED);
+    }
+    let (inner, outer) = (BELT_AU.0 as f64, BELT_AU.1 as f64);
+    AsteroidBelt::with_scale(
+        sun,
+        mode.solar_distance(inner) as f32,
+        mode.solar_distance(outer) as f32,
+        ASTEROID_COUNT,
+        ASTEROID_SEED,
+        (mode.units_per_km() / ScaleMode::Visual.units_per_km()) as f32,
+        bodies::solar_mean_motion(inner),
+    )
+}
+
+// T alterna entre órbitas suavizadas, órbitas sin suavizar y sin órbitas
+fn next_orbit_style(style: Option<LineStyle>) -> Option<LineStyle> {
+    match style {
+        Some(LineStyle::Antialiased) => Some(LineStyle::Aliased),
+        Some(LineStyle::Aliased) => None,
+        None => Some(LineStyle::Antialiased),
+    }
 }
 
 fn create_default_noise() -> FastNoiseLite {
     FastNoiseLite::with_seed(0)
 }
 
+// Fondo: estructura de la Vía Láctea, franjas de polvo, densidad y tono de las nebulosas
+fn create_galaxy_noises() -> Vec<FastNoiseLite> {
+    let mut band_noise = FastNoiseLite::with_seed(7);
+    band_noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+    band_noise.set_frequency(Some(4.0));
+    band_noise.set_fractal_type(Some(FractalType::FBm));
+    band_noise.set_fractal_octaves(Some(5));
+
+    let mut dust_noise = FastNoiseLite::with_seed(8);
+    dust_noise.set_noise_type(Some(NoiseType::Perlin));
+    dust_noise.set_frequency(Some(6.0));
+    dust_noise.set_fractal_type(Some(FractalType::FBm));
+    dust_noise.set_fractal_octaves(Some(4));
+
+    let mut nebula_noise = FastNoiseLite::with_seed(9);
+    nebula_noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+    nebula_noise.set_frequency(Some(2.0));
+    nebula_noise.set_fractal_type(Some(FractalType::FBm));
+    nebula_noise.set_fractal_octaves(Some(5));
+
+    let mut hue_noise = FastNoiseLite::with_seed(10);
+    hue_noise.set_noise_type(Some(NoiseType::Perlin));
+    hue_noise.set_frequency(Some(1.5));
+
+    vec![band_noise, dust_noise, nebula_noise, hue_noise]
+}
+
+// Manchas de la superficie de los asteroides
+fn create_asteroid_noises() -> Vec<FastNoiseLite> {
+    let mut mottling_noise = FastNoiseLite::with_seed(433);
+    mottling_noise.set_noise_type(Some(NoiseType::Cellular));
+    mottling_noise.set_frequency(Some(3.0));
+    mottling_noise.set_fractal_type(Some(FractalType::FBm));
+    mottling_noise.set_fractal_octaves(Some(2));
+
+    vec![mottling_noise]
+}
+
 fn create_lava_noise() -> Vec<FastNoiseLite> {
     let mut noise = FastNoiseLite::with_seed(42);
 
@@ -393,15 +672,6 @@ fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
     look_at(&eye, &center, &up)
 }
 
-fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
-    let fov = 45.0 * PI / 180.0;
-    let aspect_ratio = window_width / window_height;
-    let near = 0.1;
-    let far = 1000.0;
-
-    perspective(fov, aspect_ratio, near, far)
-}
-
 fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
     Mat4::new(
         width / 2.0,
@@ -423,68 +693,321 @@ fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
     )
 }
 
-fn render(
+type ShaderFn = fn(&Fragment, &Uniforms) -> Color;
+
+const BAKED_TEXTURES_DIR: &str = "assets/textures/baked";
+
+// Color y, si existe, mapa de normales del planeta. Un mapa real tiene prioridad
+// sobre el horneado con `cargo run -- bake`; sin color no se usa ninguno.
+fn load_planet_textures(name: &str) -> Vec<Texture> {
+    let load_first = |paths: Vec<String>| paths.iter().find_map(|path| Texture::load(path).ok());
+    let with_mipmaps = |texture: Texture| texture.with_filter(FilterMode::Trilinear).generate_mipmaps();
+
+    let color = load_first(vec![
+        format!("assets/textures/{}.png", name),
+        format!("assets/textures/{}.jpg", name),
+        format!("assets/textures/{}.jpeg", name),
+    ])
+    .or_else(|| load_baked(name, "color", cube_to_equirectangular))
+    .map(with_mipmaps);
+    let normal = load_first(vec![format!("assets/textures/{}_normal.png", name)])
+        .or_else(|| load_baked(name, "normal", cube_normals_to_equirectangular))
+        .map(with_mipmaps);
+
+    match color {
+        Some(color) => std::iter::once(color).chain(normal).collect(),
+        None => vec![],
+    }
+}
+
+// Mapa de alturas horneado del planeta, que reemplaza a su función de altura
+// al desplazar los vértices
+fn load_baked_height(name: &str) -> Option<Texture> {
+    load_baked(name, "height", cube_to_equirectangular)
+}
+
+// Mapa `<name>_<kind>` horneado con `cargo run -- bake`. Los cube maps se pasan
+// a equirectangular al cargarlos, con `from_cube`, porque los shaders solo
+// muestrean ese formato.
+fn load_baked(name: &str, kind: &str, from_cube: fn(&CubeMap, usize) -> Texture) -> Option<Texture> {
+    let name = format!("{}_{}", name, kind);
+    if let Ok(texture) = Texture::load(&format!("{}/{}.png", BAKED_TEXTURES_DIR, name)) {
+        return Some(texture);
+    }
+    let cube = CubeMap::load(BAKED_TEXTURES_DIR, &name).ok()?;
+    Some(from_cube(&cube, cube.size() * 4))
+}
+
+// Capas estáticas de cada planeta que se pueden hornear
+fn bake_targets() -> Vec<(&'static str, SurfaceFn, Option<HeightFn>, Vec<FastNoiseLite>)> {
+    vec![
+        ("earth", surface_earth, Some(height_earth), create_earth_noises()),
+        ("moon", surface_moon, Some(height_moon), create_moon_noises()),
+        ("venus", surface_venus, None, create_venus_noises()),
+        ("mercury", surface_mercury, Some(height_mercury), create_mercury_noises()),
+        ("jupiter", surface_jupiter, None, create_jupiter_noise()),
+        ("mars", surface_mars, Some(height_mars), create_mars_noises()),
+        ("phobos", surface_phobos, Some(height_phobos), create_phobos_noises()),
+        ("saturn", surface_saturn, None, create_saturn_noises()),
+        ("uranus", surface_uranus, None, create_uranus_noises()),
+        ("neptune", surface_neptune, None, create_neptune_noises()),
+        ("pluto", surface_pluto, None, create_pluto_noises()),
+        ("eris", surface_eris, None, create_eris_noises()),
+        ("sedna", surface_sedna, None, create_sedna_noises()),
+    ]
+}
+
+// cargo run --release -- bake <planeta|all> [resolución] [equirect|cube]
+fn run_bake(args: &[String]) {
+    let target = args.first().map(String::as_str).unwrap_or("all");
+    let resolution = args
+        .get(1)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1024);
+    let layout = match args.get(2).map(String::as_str) {
+        Some("cube") => BakeLayout::CubeMap,
+        _ => BakeLayout::Equirectangular,
+    };
+    let settings = BakeSettings {
+        layout,
+        resolution,
+        radius: SPHERE_RADIUS,
+        normal_strength: 20.0,
+    };
+
+    let mut found = false;
+    for (name, surface_fn, height_fn, noises) in bake_targets() {
+        if target != "all" && target != name {
+            continue;
+        }
+        found = true;
+
+        let uniforms = Uniforms {
+            model_matrix: Mat4::identity(),
+            view_matrix: Mat4::identity(),
+            projection_matrix: Mat4::identity(),
+            viewport_matrix: Mat4::identity(),
+            time: 0.0,
+            noises: noises.iter().collect(),
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: DepthRange::default(),
+            debug: DebugView::Shaded,
+            object_id: 0,
+        };
+
+        let start = Instant::now();
+        let baked = bake(surface_fn, height_fn, &uniforms, &settings);
+        match baked.save(BAKED_TEXTURES_DIR, name) {
+            Ok(()) => println!(
+                "{}: {:?} {} en {:.1}s",
+                name,
+                layout,
+                resolution,
+                start.elapsed().as_secs_f32()
+            ),
+            Err(error) => eprintln!("{}: no se pudo guardar: {}", name, error),
+        }
+    }
+
+    if !found {
+        eprintln!("Planeta desconocido: {}", target);
+    }
+}
+
+fn textured_or(textures: &[Texture], shader_fn: ShaderFn) -> ShaderFn {
+    if !textures.is_empty() {
+        shader_textured
+    } else {
+        shader_fn
+    }
+}
+
+fn render<F>(framebuffer: &mut Framebuffer, uniforms: &Uniforms, mesh: &Mesh, shader_fn: F)
+where
+    F: Fn(&Fragment, &Uniforms) -> Color,
+{
+    // Vertex Shader Stage: each unique vertex is transformed once, on first use
+    let mut vertex_cache = VertexCache::new(mesh, |vertex| vertex_shader(vertex, uniforms));
+
+    // Primitive Assembly and Rasterization Stage
+    let mut fragments = Vec::new();
+    let mut wireframe = Vec::new();
+    for indices in mesh.triangle_indices() {
+        let vertices = vertex_cache.fetch_triangle(indices);
+        rasterize(framebuffer, uniforms, vertices, &mut fragments, &mut wireframe);
+    }
+
+    // Fragment Processing Stage
+    for fragment in &fragments {
+        shade_fragment(framebuffer, uniforms, fragment, &shader_fn);
+    }
+
+    debug::draw_wireframe(framebuffer, &wireframe, &uniforms.depth);
+}
+
+// Dibuja la misma malla una vez por instancia en una sola pasada. El vertex
+// shader transforma la malla compartida con la matriz de cada instancia, que
+// se aplica después de la de `uniforms`; luego un único recorrido de los
+// índices rasteriza cada triángulo para todas las instancias, y el tinte de
+// cada una multiplica el color del shader.
+fn render_instanced(
     framebuffer: &mut Framebuffer,
     uniforms: &Uniforms,
-    vertex_array: &[Vertex],
-    shader_fn: fn(&Fragment, &Uniforms) -> Color,
+    mesh: &Mesh,
+    instances: &[Instance],
+    shader_fn: ShaderFn,
 ) {
-    // Vertex Shader Stage
-    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let transformed = vertex_shader(vertex, uniforms);
-        transformed_vertices.push(transformed);
+    let model_matrices: Vec<Mat4> = instances
+        .iter()
+        .map(|instance| uniforms.model_matrix * instance.model_matrix)
+        .collect();
+    let mut instance_uniforms = uniforms.clone();
+
+    // Vertex Shader Stage: vertices of instance i start at i * vertex_count
+    let vertex_count = mesh.vertices.len();
+    let mut vertices = Vec::with_capacity(vertex_count * instances.len());
+    for model_matrix in &model_matrices {
+        instance_uniforms.model_matrix = *model_matrix;
+        vertices.extend(mesh.vertices.iter().map(|vertex| vertex_shader(vertex, &instance_uniforms)));
     }
 
-    // Primitive Assembly Stage
-    let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
+    // Primitive Assembly and Rasterization Stage
+    let mut fragments: Vec<Vec<Fragment>> = instances.iter().map(|_| Vec::new()).collect();
+    let mut wireframe = Vec::new();
+    for indices in mesh.triangle_indices() {
+        for (instance, instance_fragments) in fragments.iter_mut().enumerate() {
+            let base = instance * vertex_count;
+            let triangle = indices.map(|index| &vertices[base + index]);
+            rasterize(framebuffer, uniforms, triangle, instance_fragments, &mut wireframe);
         }
     }
 
-    // Rasterization Stage
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
+    // Fragment Processing Stage
+    for ((instance, instance_fragments), model_matrix) in instances.iter().zip(&fragments).zip(&model_matrices) {
+        instance_uniforms.model_matrix = *model_matrix;
+        instance_uniforms.object_id = uniforms.object_id + instance.id;
+        let tinted = |fragment: &Fragment, uniforms: &Uniforms| {
+            shader_fn(fragment, uniforms).blend_multiply(&instance.tint)
+        };
+        for fragment in instance_fragments {
+            shade_fragment(framebuffer, &instance_uniforms, fragment, &tinted);
+        }
     }
 
-    // Fragment Processing Stage
-    for fragment in fragments {
-        let x = fragment.position.x as usize;
-        let y = fragment.position.y as usize;
-        if x < framebuffer.width && y < framebuffer.height {
-            // Aplicar el shader específico
-            let shaded_color = shader_fn(&fragment, &uniforms);
-            let color = shaded_color.to_hex();
-            framebuffer.set_current_color(color);
-            framebuffer.point(x, y, fragment.depth);
+    debug::draw_wireframe(framebuffer, &wireframe, &uniforms.depth);
+}
+
+// Rasteriza un triángulo ya transformado, recortado antes si cruza el plano
+// cercano o el lejano
+fn rasterize(
+    framebuffer: &Framebuffer,
+    uniforms: &Uniforms,
+    vertices: [&Vertex; 3],
+    fragments: &mut Vec<Fragment>,
+    wireframe: &mut Vec<[Vec3; 3]>,
+) {
+    let mut emit = |v1: &Vertex, v2: &Vertex, v3: &Vertex| {
+        fragments.extend(triangle(v1, v2, v3, framebuffer.width, framebuffer.height));
+        if uniforms.debug == DebugView::Wireframe {
+            wireframe.push([v1, v2, v3].map(|vertex| vertex.transformed_position));
+        }
+    };
+    if is_inside_depth_range(vertices, &uniforms.depth) {
+        emit(vertices[0], vertices[1], vertices[2]);
+    } else {
+        for [v1, v2, v3] in clip_triangle(vertices, uniforms) {
+            emit(&v1, &v2, &v3);
         }
     }
 }
 
+fn shade_fragment<F>(framebuffer: &mut Framebuffer, uniforms: &Uniforms, fragment: &Fragment, shader_fn: &F)
+where
+    F: Fn(&Fragment, &Uniforms) -> Color,
+{
+    let x = fragment.position.x as usize;
+    let y = fragment.position.y as usize;
+    if x < framebuffer.width && y < framebuffer.height {
+        framebuffer.overdraw[y * framebuffer.width + x] += 1;
+        // Aplicar el shader específico, salvo en las vistas de depuración que lo sustituyen
+        let shaded_color = uniforms
+            .debug
+            .fragment_color(fragment, uniforms)
+            .unwrap_or_else(|| shader_fn(fragment, uniforms));
+        let color = shaded_color.to_hex();
+        framebuffer.set_current_color(color);
+        framebuffer.point(x, y, fragment.depth);
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bake") {
+        run_bake(&args[2..]);
+        return;
+    }
+
+    // cargo run --release -- play <recorrido>
+    // cargo run --release -- render <recorrido> [carpeta] [fps]
+    let (camera_path_file, mut headless) = match args.get(1).map(String::as_str) {
+        Some("play") => (args.get(2).cloned().unwrap_or(RECORDED_PATH.to_string()), None),
+        Some("render") => {
+            let Some(file) = args.get(2) else {
+                eprintln!("Uso: render <recorrido> [carpeta] [fps]");
+                return;
+            };
+            let headless = HeadlessRender {
+                out_dir: args.get(3).cloned().unwrap_or("renders".to_string()),
+                fps: args.get(4).and_then(|value| value.parse().ok()).unwrap_or(30.0),
+                frame: 0,
+            };
+            (file.clone(), Some(headless))
+        }
+        _ => (RECORDED_PATH.to_string(), None),
+    };
+
+    // En vivo un recorrido que aún no existe empieza vacío y se llena con K
+    let mut camera_path = match CameraPath::load(&camera_path_file) {
+        Ok(camera_path) => camera_path,
+        Err(error) if headless.is_some() || error.kind() != io::ErrorKind::NotFound => {
+            eprintln!("{}: {}", camera_path_file, error);
+            if headless.is_some() {
+                return;
+            }
+            CameraPath::default()
+        }
+        Err(_) => CameraPath::default(),
+    };
+    if let Some(headless) = &headless {
+        if let Err(error) = fs::create_dir_all(&headless.out_dir) {
+            eprintln!("{}: {}", headless.out_dir, error);
+            return;
+        }
+    }
+
     let window_width = 800;
     let window_height = 800;
     let framebuffer_width = 800;
     let framebuffer_height = 800;
 
     let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
-    let mut window = Window::new(
-        "Sistema Solar - Tierra y Júpiter",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    )
-    .unwrap();
-
-    window.set_position(500, 500);
-    window.update();
+    let mut window = if headless.is_none() {
+        let mut window = Window::new(
+            "Sistema Solar - Tierra y Júpiter",
+            window_width,
+            window_height,
+            WindowOptions::default(),
+        )
+        .unwrap();
+
+        window.set_position(500, 500);
+        window.update();
+        Some(window)
+    } else {
+        None
+    };
 
     framebuffer.set_background_color(0x000000);
 
@@ -495,15 +1018,17 @@ fn main() {
         Vec3::new(0.0, 1.0, 0.0),
     );
 
-    // Cargar el modelo de esfera
-    let obj = Obj::load("assets/models/sphere.obj").expect("Failed to load obj");
+    // Icoesferas de 20 a 20480 triángulos; cada cuerpo usa la más simple
+    // cuyas aristas no pasen de 12 píxeles en pantalla
+    let sphere_lod = SphereLod::new(SphereKind::Icosphere, 5, 12.0);
+    // Los gigantes gaseosos usan esferas UV, cuyas filas siguen sus bandas
+    let banded_sphere_lod = SphereLod::new(SphereKind::UvSphere, 5, 12.0);
 
     // Configuraciones de los planetas
 
     let translation_sun = Vec3::new(-13.0, 0.0, 0.0); // Centered in the solar system
     let scale_sun = 5.0; // Large scale to represent the Sun's size
     let sun_noises = create_lava_noise(); // Vec<FastNoiseLite>
-    let vertex_array_sun = obj.get_vertex_array();
     let rotation_sun = Vec3::new(0.0, 0.0, 0.0); // No rotation needed for visual effect
 
     // Tierra
@@ -511,23 +1036,20 @@ fn main() {
     let rotation_earth = Vec3::new(0.0, 0.0, 0.0);
     let scale_earth = 1.0f32;
     let earth_noises = create_earth_noises(); // Vec<FastNoiseLite>
-    let vertex_array_earth = obj.get_vertex_array();
 
     // Júpiter
     let translation_jupiter = Vec3::new(6.0, 0.0, 0.0);
     let rotation_jupiter = Vec3::new(0.0, 0.0, 0.0);
     let scale_jupiter = 2.0f32;
     let noise_jupiter = create_jupiter_noise(); // FastNoiseLite
-    let vertex_array_jupiter = obj.get_vertex_array();
 
     // Luna
     let distance_moon = 1.0; // Distancia desde la Tierra
     let scale_moon = 0.50f32; // Tamaño relativo de la luna respecto a la Tierra
     let moon_noises = create_moon_noises();
-    let vertex_array_moon = obj.get_vertex_array();
 
     let ring_obj = Obj::load("assets/models/ring.obj").expect("Failed to load ring obj");
-    let vertex_array_ring = ring_obj.get_vertex_array();
+    let ring_mesh = ring_obj.get_mesh();
     let scale_ring = scale_moon * 0.75; // Ajusta el tamaño del anillo relativo a la Luna
     let scale_ring2 = scale_moon * 0.75; // Ajusta el tamaño del anillo relativo a la Luna
 
@@ -543,102 +1065,160 @@ fn main() {
     let translation_venus = Vec3::new(-6.0, 0.0, 0.0); // Ajusta la posición según necesites
     let rotation_venus = Vec3::new(0.0, 0.0, 0.0); // Sin rotación inicial
     let scale_venus = 0.95f32; // Tamaño relativo de Venus comparado con la Tierra
-    let vertex_array_venus = obj.get_vertex_array();
 
     // Posición, rotación y escala para Mercurio
     let translation_mercury = Vec3::new(-8.0, 0.0, 0.0); // Ajusta la posición según necesites
     let rotation_mercury = Vec3::new(0.0, 0.0, 0.0); // Sin rotación inicial
     let scale_mercury = 0.38f32; // Tamaño relativo de Mercurio comparado con la Tierra
-    let vertex_array_mercury = obj.get_vertex_array();
 
     // Posición, rotación y escala para Marte
     let translation_mars = Vec3::new(0.0, 0.0, 0.0);
     let rotation_mars = Vec3::new(0.0, 0.0, 0.0);
     let scale_mars = 1.88f32; // Tamaño relativo de Marte comparado con la Tierra
     let mars_noises = create_mars_noises();
-    let vertex_array_mars = obj.get_vertex_array();
 
     // Posición, rotación y escala para Phobos
     let rotation_phobos = Vec3::new(0.0, 0.0, 0.0);
     let scale_phobos = 0.33f32; // Tamaño relativo de Phobos comparado con la Luna
     let phobos_noises = create_phobos_noises();
-    let vertex_array_phobos = obj.get_vertex_array();
 
     // Saturn
     let translation_saturn = Vec3::new(12.0, 0.0, 0.0); // Position Saturn further out
-    let rotation_saturn = Vec3::new(0.0, 0.0, 0.0); // No initial rotation
+    let rotation_saturn = Vec3::new(0.33, 0.0, 0.33); // Axis tilted 26.7°, towards the Sun and the camera
     let scale_saturn = 1.5f32; // Relative size of Saturn compared to Earth
     let saturn_noises = create_saturn_noises(); // Assuming create_saturn_noises() is defined
-    let vertex_array_saturn = obj.get_vertex_array(); // Use the same sphere model
-
-    // Saturn's Rings
-    let translation_rings = Vec3::new(12.0, 0.0, 0.0); // Align rings with Saturn's position
-    let vertex_array_rings = ring_obj.get_vertex_array(); // Use a different model if rings are unique
 
-    let num_rings = 6; // Número de anillos que quieres generar
-    let base_scale = 2.0f32; // Escala inicial para el primer anillo
-    let scale_increment = 0.1f32; // Incremento de escala entre anillos consecutivos
-    let base_rotation = Vec3::new(0.0, 1.0, 0.0); // Rotación inicial
-    let rotation_increment = 0.015; // Incremento en la rotación en el eje Y entre anillos
+    // Saturn's Rings, in its equatorial plane
+    let mut saturn_rings = RingSystem::new(translation_sun, &SATURN_RINGS, SATURN_RINGS_SEED);
 
     // Configuraciones para Urano
     let translation_urano = Vec3::new(15.0, 0.0, 0.0); // Ajusta según la disposición de tu sistema
     let rotation_urano = Vec3::new(0.0, 0.0, 0.0);
     let scale_urano = 1.2f32; // Tamaño relativo de Urano comparado con la Tierra
     let urano_noises = create_uranus_noises(); // Asumiendo que está definido
-    let vertex_array_urano = obj.get_vertex_array();
 
     // Configuraciones para el Anillo de Urano
     let translation_urano_ring = Vec3::new(15.0, 0.0, 0.0);
     let rotation_urano_ring = Vec3::new(0.0, 0.1, 1.0); // Los anillos de Urano son notablemente inclinados
     let scale_urano_ring = 1.8f32; // Escala del anillo respecto a Urano
     let urano_ring_noises = create_uranus_ring_noises(); // Asumiendo que está definido
-    let vertex_array_urano_ring = ring_obj.get_vertex_array(); // Asumiendo que cargaste un modelo para los anillos
 
     // Neptuno
     let translation_neptune = Vec3::new(17.0, 0.0, 0.0);
     let rotation_neptune = Vec3::new(0.0, 0.0, 0.0);
     let scale_neptune = 1.6f32;
     let neptune_noises = create_neptune_noises(); // Assuming create_neptune_noises() is defined
-    let vertex_array_neptune = obj.get_vertex_array();
 
     // Plutón
     let translation_pluto = Vec3::new(19.0, 0.0, 0.0);
     let rotation_pluto = Vec3::new(0.0, 0.0, 0.0);
     let scale_pluto = 0.80f32;
-    let vertex_array_pluto = obj.get_vertex_array();
 
     // Eris
     let translation_eris = Vec3::new(21.0, 0.0, 0.0);
     let rotation_eris = Vec3::new(0.0, 0.0, 0.0);
     let scale_eris = 0.75f32;
-    let vertex_array_eris = obj.get_vertex_array();
 
     // Sedna
     let translation_sedna = Vec3::new(23.0, 0.0, 0.0);
     let rotation_sedna = Vec3::new(0.0, 0.0, 0.0);
     let scale_sedna = 0.60f32;
-    let vertex_array_sedna = obj.get_vertex_array();
+
+    // Escala de tamaños y distancias: la visual de siempre, la real o la
+    // logarítmica. Fuera de la visual la escena se centra en el cuerpo enfocado.
+    let mut scale_mode = ScaleMode::Visual;
+    let mut scene_origin = SUN;
+    let mut refocus: Option<usize> = None;
+
+    // Cinturón de asteroides alrededor del Sol, entre Marte y Júpiter
+    let mut asteroid_belt = create_asteroid_belt(scale_mode, translation_sun);
+    let asteroid_noises = create_asteroid_noises();
+    let mut show_asteroids = true;
+
+    // Cometa con sus colas y partículas del Sol (viento solar y fulguraciones)
+    let mut comet = Comet::new(translation_sun, COMET_ORBIT, COMET_SEED);
+    let rotation_comet = Vec3::new(0.3, 0.0, 0.5);
+    let scale_comet = 0.3f32;
+    let mut solar_activity =
+        SolarActivity::new(translation_sun, SPHERE_RADIUS * scale_sun, SOLAR_ACTIVITY_SEED);
+    let mut show_particles = true;
 
     // Skybox
-    let skybox = Skybox::new(5000);
+    let mut skybox = load_skybox();
+    match Backdrop::load(BACKDROP_DIR, BACKDROP_NAME) {
+        Ok(backdrop) => skybox = skybox.with_backdrop(backdrop),
+        Err(image::ImageError::IoError(error)) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => eprintln!("{}/{}: {}", BACKDROP_DIR, BACKDROP_NAME, error),
+    }
+    // Vía Láctea y nebulosas, horneadas en un cube map para no evaluar el ruido en cada píxel
+    let mut background = Background::new(create_galaxy_noises());
+    background.bake(BACKGROUND_CUBE_SIZE);
+    let mut show_background = true;
+    let mut show_constellations = true;
+
+    // Nave del jugador, dibujada con los materiales de su MTL
+    let ship_obj = Obj::load("assets/models/SpaceShip.obj").expect("Failed to load ship obj");
+    let ship_meshes = ship_obj.get_material_meshes();
+    let mut ship = Ship::new(Vec3::new(0.0, 1.0, 20.0), Vec3::new(0.0, 0.0, -1.0));
+    let mut camera_mode = CameraMode::Orbit;
+    let mut last_mouse_pos: Option<(f32, f32)> = None;
+    let mut right_was_down = false;
+    let mut focus: Option<CameraFocus> = None;
+    // Vista (desplazamiento del ojo y vector arriba) a restaurar al salir del planetario
+    let mut saved_view = (Vec3::new(0.0, 0.0, 25.0), Vec3::new(0.0, 1.0, 0.0));
+
+    // Mapas de textura opcionales en assets/textures; si no existen se usa el shader procedural
+    let earth_textures = load_planet_textures("earth");
+    let moon_textures = load_planet_textures("moon");
+    let venus_textures = load_planet_textures("venus");
+    let mercury_textures = load_planet_textures("mercury");
+    let jupiter_textures = load_planet_textures("jupiter");
+    let mars_textures = load_planet_textures("mars");
+    let phobos_textures = load_planet_textures("phobos");
+    let saturn_textures = load_planet_textures("saturn");
+    let uranus_textures = load_planet_textures("uranus");
+    let neptune_textures = load_planet_textures("neptune");
+    let pluto_textures = load_planet_textures("pluto");
+    let eris_textures = load_planet_textures("eris");
+    let sedna_textures = load_planet_textures("sedna");
+    // Alturas horneadas de los cuerpos con relieve desplazado
+    let earth_height = load_baked_height("earth");
+    let moon_height = load_baked_height("moon");
+    let mercury_height = load_baked_height("mercury");
+    let mars_height = load_baked_height("mars");
 
-    let projection_matrix = create_perspective_matrix(window_width as f32, window_height as f32);
     let viewport_matrix =
         create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
 
     let mut time = 0.0f32;
+    // Multiplica el avance del tiempo simulado en cada frame
+    let mut time_warp = 1.0f32;
+    let mut playback_time = 0.0f32;
+    // Los recorridos sin ventana son para vídeo, sin HUD
+    let mut hud = Hud::default();
+    let mut orbit_style = Some(LineStyle::Antialiased);
+    let mut debug_view = DebugView::Shaded;
+    if headless.is_some() {
+        camera_mode = CameraMode::Path;
+        hud.visible = false;
+    }
 
-    while window.is_open() {
-        if window.is_key_down(Key::Escape) {
-            break;
-        }
-
-        time += 100.0;
-
-        handle_input(&window, &mut camera);
+    loop {
+        // Calcular delta_time; sin ventana los frames avanzan a paso fijo
+        let delta_time = match &headless {
+            Some(headless) => 1.0 / headless.fps,
+            None => {
+                let current_time = Instant::now();
+                let delta_time = (current_time - previous_time).as_secs_f32();
+                previous_time = current_time;
+                delta_time
+            }
+        };
 
-        framebuffer.clear();
+        time += 100.0 * time_warp;
+        hud.update(delta_time);
+        ring1_angle += ring1_rotation_speed * delta_time;
+        ring2_angle += ring2_rotation_speed * delta_time;
 
         // Calcular la posición de la luna orbitando alrededor de la Tierra
         let moon_orbit_speed = 0.005; // Velocidad de órbita de la luna
@@ -652,13 +1232,6 @@ fn main() {
 
         let rotation_moon = Vec3::new(0.0, angle, 0.0);
 
-        // Calcular delta_time
-        let current_time = Instant::now();
-        let delta_time = (current_time - previous_time).as_secs_f32();
-        previous_time = current_time;
-        ring1_angle += ring1_rotation_speed * delta_time;
-        ring2_angle += ring2_rotation_speed * delta_time;
-
         let phobos_orbit_speed = 0.0002; // Ajusta la velocidad de la órbita
         let phobos_distance_from_mars = 2.0; // Distancia de Phobos a Marte
         let phobos_orbit_angle = time * phobos_orbit_speed;
@@ -670,17 +1243,321 @@ fn main() {
             translation_mars.z,
         );
 
-        // Renderizar el Skybox
+        // Fuera de la escala visual los cuerpos siguen sus órbitas reales. Al
+        // cambiar el cuerpo en el centro de la escena la cámara se mueve con
+        // ella, así que no se nota.
+        let mut layout = (scale_mode != ScaleMode::Visual).then(|| Layout::new(scale_mode, time, translation_sun));
+        match layout.as_mut() {
+            Some(layout) => {
+                let origin = focus
+                    .as_ref()
+                    .map(|focus| focus.target)
+                    .filter(|&target| target < BODY_COUNT)
+                    .unwrap_or(SUN);
+                layout.recentre(scene_origin);
+                if origin != scene_origin {
+                    let shift = layout.recentre(origin);
+                    camera.eye += shift;
+                    camera.center += shift;
+                    camera.has_changed = true;
+                    if let Some(focus) = focus.as_mut() {
+                        focus.shift(shift);
+                    }
+                    scene_origin = origin;
+                }
+            }
+            None => scene_origin = SUN,
+        }
+        // Lugar, tamaño y giro de cada cuerpo de `BODIES`: en la escala visual
+        // los de siempre, buscados por nombre, y si no los de `layout`
+        let visual = [
+            ("Sol", translation_sun, scale_sun, rotation_sun),
+            ("Mercurio", translation_mercury, scale_mercury, rotation_mercury),
+            ("Venus", translation_venus, scale_venus, rotation_venus),
+            ("Tierra", translation_earth, scale_earth, rotation_earth),
+            ("Luna", moon_translation, scale_moon, rotation_moon),
+            ("Marte", translation_mars, scale_mars, rotation_mars),
+            ("Fobos", phobos_translation, scale_phobos, rotation_phobos),
+            ("Júpiter", translation_jupiter, scale_jupiter, rotation_jupiter),
+            ("Saturno", translation_saturn, scale_saturn, rotation_saturn),
+            ("Urano", translation_urano, scale_urano, rotation_urano),
+            ("Neptuno", translation_neptune, scale_neptune, rotation_neptune),
+            ("Plutón", translation_pluto, scale_pluto, rotation_pluto),
+            ("Eris", translation_eris, scale_eris, rotation_eris),
+            ("Sedna", translation_sedna, scale_sedna, rotation_sedna),
+        ];
+        let placements: [(Vec3, f32, Vec3); BODY_COUNT] = std::array::from_fn(|index| match &layout {
+            Some(layout) => (layout.position(index), layout.scale(index), layout.rotation(index)),
+            None => {
+                let name = BODIES[index].name;
+                let &(_, translation, scale, rotation) = visual
+                    .iter()
+                    .find(|(visual_name, ..)| *visual_name == name)
+                    .unwrap_or_else(|| panic!("{} no tiene lugar en la escala visual", name));
+                (translation, scale, rotation)
+            }
+        });
+        let body_id = |name: &str| match body_index(name) {
+            Some(index) => index as u32,
+            None => panic!("{} no está en BODIES", name),
+        };
+        let place = |name: &str| placements[body_id(name) as usize];
+        let (translation_sun, scale_sun, rotation_sun) = place("Sol");
+        let (translation_mercury, scale_mercury, rotation_mercury) = place("Mercurio");
+        let (translation_venus, scale_venus, rotation_venus) = place("Venus");
+        let (translation_earth, scale_earth, rotation_earth) = place("Tierra");
+        let (moon_translation, scale_moon, rotation_moon) = place("Luna");
+        let (translation_mars, scale_mars, rotation_mars) = place("Marte");
+        let (phobos_translation, scale_phobos, rotation_phobos) = place("Fobos");
+        let (translation_jupiter, scale_jupiter, rotation_jupiter) = place("Júpiter");
+        let (translation_saturn, scale_saturn, rotation_saturn) = place("Saturno");
+        let (translation_urano, scale_urano, rotation_urano) = place("Urano");
+        let (translation_neptune, scale_neptune, rotation_neptune) = place("Neptuno");
+        let (translation_pluto, scale_pluto, rotation_pluto) = place("Plutón");
+        let (translation_eris, scale_eris, rotation_eris) = place("Eris");
+        let (translation_sedna, scale_sedna, rotation_sedna) = place("Sedna");
+        // El anillo de Urano gira con el planeta, con el tamaño relativo de la escala visual
+        let (translation_urano_ring, scale_urano_ring, rotation_urano_ring) = match &layout {
+            Some(_) => (translation_urano, scale_urano * 1.5, rotation_urano),
+            None => (translation_urano_ring, scale_urano_ring, rotation_urano_ring),
+        };
+        asteroid_belt.set_center(translation_sun);
+        saturn_rings.set_sun(translation_sun);
+
+        // Cuerpos que se pueden enfocar, con su posición y radio en este frame:
+        // los de `BODIES` en su orden y, solo en la escala visual, el cometa
+        let mut bodies: Vec<(&str, Vec3, f32)> = BODIES
+            .iter()
+            .zip(&placements)
+            .map(|(body, &(position, scale, _))| (body.name, position, scale))
+            .collect();
+        if scale_mode == ScaleMode::Visual {
+            bodies.push(("Cometa", comet.position(time), scale_comet));
+        }
+        let bodies = &bodies[..];
+
+        // Tras cambiar de escala se vuelve a enfocar el mismo cuerpo en su
+        // nuevo lugar y tamaño
+        if let Some(target) = refocus.take() {
+            let (_, position, scale) = bodies[target];
+            focus = Some(CameraFocus::new(target, &camera, position, SPHERE_RADIUS * scale));
+        }
+
+        // Entrada, solo con ventana
+        let mut ship_controls = ShipControls::default();
+        if let Some(window) = &window {
+            if !window.is_open() || window.is_key_down(Key::Escape) {
+                break;
+            }
+
+            // C alterna con la cámara de persecución, V con el vuelo libre y O
+            // con la vista cenital del planetario
+            let toggled = if window.is_key_pressed(Key::C, KeyRepeat::No) {
+                Some(CameraMode::Chase)
+            } else if window.is_key_pressed(Key::V, KeyRepeat::No) {
+                Some(CameraMode::FreeFly)
+            } else if window.is_key_pressed(Key::O, KeyRepeat::No) {
+                Some(CameraMode::Orrery)
+            } else if window.is_key_pressed(Key::P, KeyRepeat::No)
+                && !camera_path.keyframes.is_empty()
+            {
+                playback_time = 0.0;
+                Some(CameraMode::Path)
+            } else {
+                None
+            };
+            if let Some(mode) = toggled {
+                let mode = if camera_mode == mode {
+                    CameraMode::Orbit
+                } else {
+                    mode
+                };
+                set_camera_mode(&mut camera, &mut camera_mode, mode, &mut saved_view);
+                focus = None;
+            }
+
+            // Corchetes: lente de zoom; L: siguiente modo de profundidad
+            if window.is_key_down(Key::LeftBracket) {
+                camera.change_fov(-0.5 * delta_time);
+            }
+            if window.is_key_down(Key::RightBracket) {
+                camera.change_fov(0.5 * delta_time);
+            }
+            if window.is_key_pressed(Key::L, KeyRepeat::No) {
+                camera.depth_mode = camera.depth_mode.next();
+            }
+
+            // Coma y punto: mitad o doble de velocidad del tiempo; H: HUD; N: etiquetas
+            if window.is_key_pressed(Key::Comma, KeyRepeat::No) {
+                time_warp = (time_warp / 2.0).max(1.0 / 64.0);
+            }
+            if window.is_key_pressed(Key::Period, KeyRepeat::No) {
+                time_warp = (time_warp * 2.0).min(64.0);
+            }
+            if window.is_key_pressed(Key::H, KeyRepeat::No) {
+                hud.visible = !hud.visible;
+            }
+            if window.is_key_pressed(Key::N, KeyRepeat::No) {
+                hud.labels = !hud.labels;
+            }
+            if window.is_key_pressed(Key::T, KeyRepeat::No) {
+                orbit_style = next_orbit_style(orbit_style);
+            }
+            // G: cinturón de asteroides
+            if window.is_key_pressed(Key::G, KeyRepeat::No) {
+                show_asteroids = !show_asteroids;
+            }
+            // U: colas del cometa, viento solar y fulguraciones
+            if window.is_key_pressed(Key::U, KeyRepeat::No) {
+                show_particles = !show_particles;
+            }
+            // J: líneas de las constelaciones
+            if window.is_key_pressed(Key::J, KeyRepeat::No) && skybox.has_constellations() {
+                show_constellations = !show_constellations;
+            }
+            // B: fondo horneado, evaluado en cada píxel u oculto
+            if window.is_key_pressed(Key::B, KeyRepeat::No) {
+                if !show_background {
+                    background.bake(BACKGROUND_CUBE_SIZE);
+                    show_background = true;
+                } else if background.is_baked() {
+                    background.unbake();
+                } else {
+                    show_background = false;
+                }
+            }
+            // Z: siguiente escala (visual, real y logarítmica)
+            if window.is_key_pressed(Key::Z, KeyRepeat::No) {
+                scale_mode = scale_mode.next();
+                asteroid_belt = create_asteroid_belt(scale_mode, translation_sun);
+                refocus = focus
+                    .take()
+                    .map(|focus| focus.target)
+                    .filter(|&target| scale_mode == ScaleMode::Visual || target < BODY_COUNT);
+            }
+            // M: siguiente vista de depuración
+            if window.is_key_pressed(Key::M, KeyRepeat::No) {
+                debug_view = debug_view.next();
+            }
+
+            // K guarda la vista actual como un nuevo keyframe del recorrido
+            if window.is_key_pressed(Key::K, KeyRepeat::No) {
+                camera_path.record(&camera);
+                match camera_path.save(&camera_path_file) {
+                    Ok(()) => println!(
+                        "Keyframe {} guardado en {}",
+                        camera_path.keyframes.len(),
+                        camera_path_file
+                    ),
+                    Err(error) => eprintln!("{}: no se pudo guardar: {}", camera_path_file, error),
+                }
+            }
+
+            match camera_mode {
+                CameraMode::Orbit => handle_input(window, &mut camera),
+                CameraMode::FreeFly => {
+                    handle_free_fly_input(window, &mut camera, &mut last_mouse_pos, delta_time)
+                }
+                CameraMode::Chase => ship_controls = handle_ship_input(window),
+                CameraMode::Orrery => handle_orrery_input(window, &mut camera, delta_time),
+                CameraMode::Path => {}
+            }
+            if camera_mode != CameraMode::FreeFly {
+                last_mouse_pos = None;
+            }
+
+            if window.is_key_pressed(Key::Backspace, KeyRepeat::No) {
+                focus = None;
+            }
+            let current_target = focus.as_ref().map(|focus| focus.target);
+            if let Some(target) = handle_focus_input(window, current_target, bodies.len()) {
+                // Enfocar un cuerpo devuelve la cámara a la órbita a su alrededor
+                set_camera_mode(&mut camera, &mut camera_mode, CameraMode::Orbit, &mut saved_view);
+                let (_, position, scale) = bodies[target];
+                focus = Some(CameraFocus::new(target, &camera, position, SPHERE_RADIUS * scale));
+            }
+
+            // Clic derecho: selecciona y enfoca el cuerpo bajo el cursor
+            let right_down = window.get_mouse_down(MouseButton::Right);
+            if right_down && !right_was_down {
+                let size = (framebuffer_width as f32, framebuffer_height as f32);
+                if let Some((target, _)) = pick_body(window, &camera, bodies, size) {
+                    let (_, position, scale) = bodies[target];
+                    let radius = SPHERE_RADIUS * scale;
+                    set_camera_mode(&mut camera, &mut camera_mode, CameraMode::Orbit, &mut saved_view);
+                    focus = Some(CameraFocus::new(target, &camera, position, radius));
+                }
+            }
+            right_was_down = right_down;
+        }
+
+        // La nave sigue a la deriva aunque no se esté pilotando
+        ship.update(&ship_controls, delta_time);
+        if camera_mode == CameraMode::Chase {
+            let (eye, center, up) = ship.chase_view();
+            camera.ease_towards(eye, center, up, 1.0 - (-8.0 * delta_time).exp());
+        }
+
+        if let Some(focus) = focus.as_mut() {
+            let (_, position, _) = bodies[focus.target];
+            focus.update(&mut camera, position, delta_time);
+        }
+
+        if camera_mode == CameraMode::Path {
+            if playback_time > camera_path.duration() {
+                if headless.is_some() {
+                    break;
+                }
+                set_camera_mode(&mut camera, &mut camera_mode, CameraMode::Orbit, &mut saved_view);
+            } else if let Some(keyframe) = camera_path.sample(playback_time) {
+                keyframe.apply(&mut camera);
+            }
+            playback_time += delta_time;
+        }
+
+        let nearest_surface = bodies
+            .iter()
+            .map(|&(_, position, scale)| (position - camera.eye).magnitude() - SPHERE_RADIUS * scale)
+            .fold(f32::INFINITY, f32::min);
+        camera.near = (nearest_surface * 0.5).clamp(MIN_NEAR_PLANE, MAX_NEAR_PLANE);
+
+        let depth_range = camera.depth_range();
+        framebuffer.set_reversed_depth(depth_range.is_reversed());
+        framebuffer.clear();
+
+        let projection_matrix =
+            camera.projection_matrix(framebuffer_width as f32, framebuffer_height as f32);
+        // Renderizar el Skybox, que solo depende de hacia dónde mira la cámara
         let default_noise = create_default_noise();
+        let (sky_view, sky_projection) = sky_matrices(
+            &create_view_matrix(camera.eye, camera.center, camera.up),
+            &projection_matrix,
+            &depth_range,
+        );
         let uniforms_skybox = Uniforms {
             model_matrix: Mat4::identity(),
-            view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
-            projection_matrix,
+            view_matrix: sky_view,
+            projection_matrix: sky_projection,
             viewport_matrix,
             time,
             noises: vec![&default_noise],
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: 0,
         };
-        skybox.render(&mut framebuffer, &uniforms_skybox, camera.eye);
+        // El fondo pintado, si hay, sustituye a la Vía Láctea procedural
+        if skybox.has_backdrop() {
+            skybox.render_backdrop(&mut framebuffer, &sky_view, &sky_projection, &depth_range);
+        } else if show_background {
+            background.render(&mut framebuffer, &sky_view, &sky_projection);
+        }
+        skybox.render(&mut framebuffer, &uniforms_skybox);
+        if show_constellations {
+            skybox.render_constellations(&mut framebuffer, &uniforms_skybox);
+        }
 
         let sun_noises_refs: Vec<&FastNoiseLite> = sun_noises.iter().collect();
         let uniforms_sun = Uniforms {
@@ -690,6 +1567,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: sun_noises_refs,
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Sol"),
         };
 
         // Uniforms de la Tierra
@@ -701,6 +1584,15 @@ fn main() {
             viewport_matrix,
             time,
             noises: earth_noise_refs,
+            textures: earth_textures.iter().collect(),
+            material: None,
+            displacement: Some(Displacement {
+                height: HeightSource::baked_or(earth_height.as_ref(), height_earth),
+                amplitude: 0.04,
+            }),
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Tierra"),
         };
 
         let jupiter_noise_refs: Vec<&FastNoiseLite> = noise_jupiter.iter().collect();
@@ -711,6 +1603,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: jupiter_noise_refs,
+            textures: jupiter_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Júpiter"),
         };
 
         let moon_noise_refs: Vec<&FastNoiseLite> = moon_noises.iter().collect();
@@ -721,6 +1619,15 @@ fn main() {
             viewport_matrix,
             time,
             noises: moon_noise_refs,
+            textures: moon_textures.iter().collect(),
+            material: None,
+            displacement: Some(Displacement {
+                height: HeightSource::baked_or(moon_height.as_ref(), height_moon),
+                amplitude: 0.03,
+            }),
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Luna"),
         };
 
         let rotation_ring1 = Vec3::new(0.0, 0.0, ring1_angle);
@@ -731,6 +1638,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: vec![], // Puedes agregar noises si los necesitas para el shader
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: MOON_RING_IDS[0],
         };
 
         let rotation_ring2 = Vec3::new(ring2_angle, 0.0, 0.0);
@@ -741,6 +1654,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: vec![],
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: MOON_RING_IDS[1],
         };
 
         let venus_noises = create_venus_noises();
@@ -752,6 +1671,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: venus_noises.iter().collect(),
+            textures: venus_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Venus"),
         };
 
         let mercury_noises = create_mercury_noises();
@@ -762,6 +1687,15 @@ fn main() {
             viewport_matrix,
             time,
             noises: mercury_noises.iter().collect(),
+            textures: mercury_textures.iter().collect(),
+            material: None,
+            displacement: Some(Displacement {
+                height: HeightSource::baked_or(mercury_height.as_ref(), height_mercury),
+                amplitude: 0.03,
+            }),
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Mercurio"),
         };
 
         // Crear uniforms para Marte y Phobos
@@ -772,6 +1706,15 @@ fn main() {
             viewport_matrix,
             time,
             noises: mars_noises.iter().collect(),
+            textures: mars_textures.iter().collect(),
+            material: None,
+            displacement: Some(Displacement {
+                height: HeightSource::baked_or(mars_height.as_ref(), height_mars),
+                amplitude: 0.05,
+            }),
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Marte"),
         };
 
         let uniforms_phobos = Uniforms {
@@ -781,6 +1724,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: phobos_noises.iter().collect(),
+            textures: phobos_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Fobos"),
         };
 
         // Uniforms for Saturn
@@ -791,6 +1740,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: saturn_noises.iter().collect(),
+            textures: saturn_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Saturno"),
         };
 
         // Uniforms para Urano
@@ -801,6 +1756,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: urano_noises.iter().collect(),
+            textures: uranus_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Urano"),
         };
 
         // Uniforms para el Anillo de Urano
@@ -815,6 +1776,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: urano_ring_noises.iter().collect(),
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: URANUS_RING_ID,
         };
 
         // Neptuno
@@ -826,6 +1793,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: neptune_noises.iter().collect(),
+            textures: neptune_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Neptuno"),
         };
 
         // Plutón
@@ -837,6 +1810,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: pluto_noises.iter().collect(),
+            textures: pluto_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Plutón"),
         };
 
         // Eris
@@ -848,6 +1827,12 @@ fn main() {
             viewport_matrix,
             time,
             noises: eris_noises.iter().collect(),
+            textures: eris_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Eris"),
         };
 
         // Sedna
@@ -859,12 +1844,18 @@ fn main() {
             viewport_matrix,
             time,
             noises: sedna_noises.iter().collect(),
+            textures: sedna_textures.iter().collect(),
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: body_id("Sedna"),
         };
 
         render(
             &mut framebuffer,
             &uniforms_sun,
-            &vertex_array_sun, // Assuming a vertex array for a sphere representing the Sun
+            sphere_lod.select_for(&uniforms_sun),
             fragment_shader,
         );
 
@@ -872,156 +1863,356 @@ fn main() {
         render(
             &mut framebuffer,
             &uniforms_earth,
-            &vertex_array_earth,
-            shader_earth,
+            sphere_lod.select_for(&uniforms_earth),
+            // Con textura solo las nubes y la atmósfera se animan en cada frame
+            if !earth_textures.is_empty() {
+                shader_earth_textured
+            } else {
+                shader_earth
+            },
         );
 
         // Renderizar la Luna
         render(
             &mut framebuffer,
             &uniforms_moon,
-            &vertex_array_moon,
-            shader_moon,
+            sphere_lod.select_for(&uniforms_moon),
+            textured_or(&moon_textures, shader_moon),
         );
 
-        render(
-            &mut framebuffer,
-            &uniforms_ring,
-            &vertex_array_ring,
-            shader_ring, // Crearemos este shader en el siguiente paso
-        );
+        // Los anillos de la Luna son decorativos y solo salen en la escala visual
+        if scale_mode == ScaleMode::Visual {
+            render(
+                &mut framebuffer,
+                &uniforms_ring,
+                &ring_mesh,
+                shader_ring, // Crearemos este shader en el siguiente paso
+            );
 
-        render(
-            &mut framebuffer,
-            &uniforms_ring2,
-            &vertex_array_ring,
-            shader_ring,
-        );
+            render(
+                &mut framebuffer,
+                &uniforms_ring2,
+                &ring_mesh,
+                shader_ring,
+            );
+        }
 
         render(
             &mut framebuffer,
             &uniforms_venus,
-            &vertex_array_venus,
-            shader_venus,
+            sphere_lod.select_for(&uniforms_venus),
+            textured_or(&venus_textures, shader_venus),
         );
 
         render(
             &mut framebuffer,
             &uniforms_mercury,
-            &vertex_array_mercury,
-            shader_mercury,
+            sphere_lod.select_for(&uniforms_mercury),
+            textured_or(&mercury_textures, shader_mercury),
         );
 
         // Renderizar Júpiter
         render(
             &mut framebuffer,
             &uniforms_jupiter,
-            &vertex_array_jupiter,
-            shader_jupiter,
+            banded_sphere_lod.select_for(&uniforms_jupiter),
+            textured_or(&jupiter_textures, shader_jupiter),
         );
 
         // Agregar renderizado de Marte y Phobos
         render(
             &mut framebuffer,
             &uniforms_mars,
-            &vertex_array_mars,
-            shader_mars, // Asegúrate de que shader_mars está implementado
+            sphere_lod.select_for(&uniforms_mars),
+            textured_or(&mars_textures, shader_mars),
         );
 
         render(
             &mut framebuffer,
             &uniforms_phobos,
-            &vertex_array_phobos,
-            shader_phobos, // Asegúrate de que shader_phobos está implementado
+            sphere_lod.select_for(&uniforms_phobos),
+            textured_or(&phobos_textures, shader_phobos),
         );
 
         render(
             &mut framebuffer,
             &uniforms_saturn,
-            &vertex_array_saturn,
-            shader_saturn,
+            banded_sphere_lod.select_for(&uniforms_saturn),
+            textured_or(&saturn_textures, shader_saturn),
         );
 
-        for i in 0..num_rings {
-            let scale = base_scale + (i as f32 * scale_increment);
-            let rotation = Vec3::new(
-                0.0,
-                1.0,
-                base_rotation.y
-                    + (i as f32 * rotation_increment * if i % 2 == 0 { 1.0 } else { -1.0 }),
-            );
-
-            let uniforms_ring = Uniforms {
-                model_matrix: create_model_matrix(translation_rings, scale, rotation),
-                view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
-                projection_matrix,
-                viewport_matrix,
-                time,
-                noises: vec![], // Los anillos no requieren ruido en este ajuste
-            };
-
-            render(
-                &mut framebuffer,
-                &uniforms_ring,
-                &vertex_array_rings,
-                shader_ring,
-            );
-        }
-
         // Renderizar Urano
         render(
             &mut framebuffer,
             &uniforms_urano,
-            &vertex_array_urano,
-            shader_uranus, // Asegúrate de que shader_urano está implementado
+            banded_sphere_lod.select_for(&uniforms_urano),
+            textured_or(&uranus_textures, shader_uranus),
         );
 
         // Renderizar el Anillo de Urano
         render(
             &mut framebuffer,
             &uniforms_urano_ring,
-            &vertex_array_urano_ring,
+            &ring_mesh,
             shader_uranus_ring, // Asegúrate de que shader_urano_ring está implementado
         );
 
         render(
             &mut framebuffer,
             &uniforms_neptune,
-            &vertex_array_neptune,
-            shader_neptune,
+            banded_sphere_lod.select_for(&uniforms_neptune),
+            textured_or(&neptune_textures, shader_neptune),
         );
 
         render(
             &mut framebuffer,
             &uniforms_pluto,
-            &vertex_array_pluto,
-            shader_pluto,
+            sphere_lod.select_for(&uniforms_pluto),
+            textured_or(&pluto_textures, shader_pluto),
         );
 
         render(
             &mut framebuffer,
             &uniforms_eris,
-            &vertex_array_eris,
-            shader_eris,
+            sphere_lod.select_for(&uniforms_eris),
+            textured_or(&eris_textures, shader_eris),
         );
 
         render(
             &mut framebuffer,
             &uniforms_sedna,
-            &vertex_array_sedna,
-            shader_sedna,
+            sphere_lod.select_for(&uniforms_sedna),
+            textured_or(&sedna_textures, shader_sedna),
+        );
+
+        // Asteroides: una malla por forma y nivel de detalle, dibujada una vez
+        // por roca; los lejanos son un solo punto
+        if show_asteroids {
+            let uniforms_asteroids = Uniforms {
+                model_matrix: Mat4::identity(),
+                view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+                projection_matrix,
+                viewport_matrix,
+                time,
+                noises: asteroid_noises.iter().collect(),
+                textures: vec![],
+                material: None,
+                displacement: None,
+                depth: depth_range,
+                debug: debug_view,
+                object_id: FIRST_ASTEROID_ID,
+            };
+            let belt = asteroid_belt.visible(&uniforms_asteroids);
+            for (mesh, instances) in &belt.batches {
+                render_instanced(&mut framebuffer, &uniforms_asteroids, mesh, instances, shader_asteroid);
+            }
+            belt.draw_points(&mut framebuffer);
+        }
+
+        // Núcleo del cometa, cuya órbita y colas están hechas para la escala visual
+        let uniforms_comet = Uniforms {
+            model_matrix: create_model_matrix(comet.position(time), scale_comet, rotation_comet),
+            view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+            projection_matrix,
+            viewport_matrix,
+            time,
+            noises: asteroid_noises.iter().collect(),
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: COMET_ID,
+        };
+        if scale_mode == ScaleMode::Visual {
+            render(
+                &mut framebuffer,
+                &uniforms_comet,
+                sphere_lod.select_for(&uniforms_comet),
+                shader_asteroid,
+            );
+        }
+
+        for (mesh, material) in &ship_meshes {
+            let uniforms_ship = Uniforms {
+                model_matrix: ship.model_matrix(),
+                view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+                projection_matrix,
+                viewport_matrix,
+                time,
+                noises: vec![],
+                textures: vec![],
+                material: Some(material),
+                displacement: None,
+                depth: depth_range,
+                debug: debug_view,
+                object_id: SHIP_ID,
+            };
+
+            render(&mut framebuffer, &uniforms_ship, mesh, shader_material);
+        }
+
+        // Anillos de Saturno, translúcidos sobre todo lo opaco
+        let uniforms_saturn_rings = Uniforms {
+            model_matrix: create_model_matrix(translation_saturn, SPHERE_RADIUS * scale_saturn, rotation_saturn),
+            view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+            projection_matrix,
+            viewport_matrix,
+            time,
+            noises: vec![],
+            textures: vec![],
+            material: None,
+            displacement: None,
+            depth: depth_range,
+            debug: debug_view,
+            object_id: SATURN_RINGS_ID,
+        };
+        saturn_rings.render(&mut framebuffer, &uniforms_saturn_rings);
+
+        // Partículas, sumadas sobre todo lo opaco sin escribir profundidad. Se
+        // simulan aunque estén ocultas para que no tengan que ponerse al día.
+        // Como el cometa, solo tienen sentido en la escala visual.
+        comet.update(time);
+        solar_activity.update(time);
+        if show_particles && scale_mode == ScaleMode::Visual {
+            let uniforms_particles = Uniforms {
+                model_matrix: Mat4::identity(),
+                view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+                projection_matrix,
+                viewport_matrix,
+                time,
+                noises: vec![],
+                textures: vec![],
+                material: None,
+                displacement: None,
+                depth: depth_range,
+                debug: debug_view,
+                object_id: 0,
+            };
+            solar_activity.render(&mut framebuffer, &uniforms_particles);
+            comet.render_tails(&mut framebuffer, &uniforms_particles);
+        }
+
+        // Las vistas de profundidad y sobredibujado reemplazan la imagen ya terminada
+        debug::resolve(&mut framebuffer, debug_view, &depth_range);
+
+        // Órbitas detrás de los planetas ya dibujados, con prueba de profundidad
+        if let Some(style) = orbit_style {
+            let uniforms_orbits = Uniforms {
+                model_matrix: Mat4::identity(),
+                view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+                projection_matrix,
+                viewport_matrix,
+                time,
+                noises: vec![],
+                textures: vec![],
+                material: None,
+                displacement: None,
+                depth: depth_range,
+                debug: debug_view,
+                object_id: 0,
+            };
+            let planet_color = Color::new(90, 140, 220);
+            let moon_color = Color::new(180, 180, 180);
+
+            let fade_distance = if layout.is_some() {
+                REAL_ORBIT_FADE_DISTANCE
+            } else {
+                ORBIT_FADE_DISTANCE
+            };
+
+            for (index, &(_, position, _)) in bodies.iter().enumerate() {
+                let Some((parent, axis)) = body_orbit(index) else {
+                    continue;
+                };
+                // Fuera de la escala visual, la elipse real de cada cuerpo
+                let orbit = match &layout {
+                    Some(layout) => layout.orbit(index),
+                    None => Orbit::around(bodies[parent].1, position, axis),
+                };
+                let Some(orbit) = orbit else {
+                    continue;
+                };
+                let color = if parent == SUN { &planet_color } else { &moon_color };
+                draw_orbit(
+                    &mut framebuffer,
+                    &uniforms_orbits,
+                    &orbit,
+                    &camera.eye,
+                    fade_distance,
+                    color,
+                    style,
+                );
+            }
+        }
+
+        let labels: Vec<(&str, Vec3, f32)> = bodies
+            .iter()
+            .map(|&(name, position, scale)| (name, position, SPHERE_RADIUS * scale))
+            .collect();
+        hud.draw_labels(
+            &mut framebuffer,
+            &labels,
+            &create_view_matrix(camera.eye, camera.center, camera.up),
+            &projection_matrix,
+            &viewport_matrix,
         );
+        hud.draw(
+            &mut framebuffer,
+            &HudInfo {
+                sim_time: time,
+                time_warp,
+                camera_position: camera.eye,
+                camera_mode: camera_mode.name(),
+                scale_mode: scale_mode.name(),
+                depth_mode: depth_range.mode.name(),
+                background: if !show_background {
+                    "oculto"
+                } else if background.is_baked() {
+                    "horneado"
+                } else {
+                    "procedural"
+                },
+                selected: focus.as_ref().map(|focus| {
+                    let (name, position, scale) = bodies[focus.target];
+                    let radius = SPHERE_RADIUS * scale;
+                    Selection {
+                        name,
+                        radius,
+                        distance: (camera.eye - position).magnitude() - radius,
+                        data: BODIES.get(focus.target),
+                    }
+                }),
+                debug_view: (debug_view != DebugView::Shaded).then(|| debug_view.name()),
+            },
+        );
+
+        match (&mut window, &mut headless) {
+            (Some(window), _) => window
+                .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
+                .unwrap(),
+            (None, Some(headless)) => {
+                let path = format!("{}/frame_{:05}.png", headless.out_dir, headless.frame);
+                if let Err(error) = framebuffer.save(&path) {
+                    eprintln!("{}: no se pudo guardar: {}", path, error);
+                    break;
+                }
+                headless.frame += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
 
-        window
-            .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
-            .unwrap();
+    if let Some(headless) = &headless {
+        println!("{} frames en {}", headless.frame, headless.out_dir);
     }
 }
 
 fn handle_input(window: &Window, camera: &mut Camera) {
     let movement_speed = 1.0;
     let rotation_speed = PI / 50.0;
-    let zoom_speed = 0.1;
+    // Más despacio cerca del centro, para acercarse a cuerpos diminutos sin atravesarlos
+    let zoom_speed = (0.05 * (camera.center - camera.eye).magnitude()).min(0.1);
 
     // Controles de órbita de la cámara
     if window.is_key_down(Key::Left) {
@@ -1063,3 +2254,207 @@ fn handle_input(window: &Window, camera: &mut Camera) {
         camera.zoom(-zoom_speed);
     }
 }
+
+fn handle_ship_input(window: &Window) -> ShipControls {
+    let axis = |positive: Key, negative: Key| {
+        let mut value = 0.0;
+        if window.is_key_down(positive) {
+            value += 1.0;
+        }
+        if window.is_key_down(negative) {
+            value -= 1.0;
+        }
+        value
+    };
+
+    ShipControls {
+        thrust: axis(Key::W, Key::S),
+        strafe_right: axis(Key::D, Key::A),
+        strafe_up: axis(Key::R, Key::F),
+        pitch: axis(Key::Down, Key::Up),
+        yaw: axis(Key::Left, Key::Right),
+        roll: axis(Key::E, Key::Q),
+        brake: window.is_key_down(Key::X),
+    }
+}
+
+fn handle_free_fly_input(
+    window: &Window,
+    camera: &mut Camera,
+    last_mouse_pos: &mut Option<(f32, f32)>,
+    delta_time: f32,
+) {
+    let mouse_sensitivity = 0.005;
+    let roll_speed = PI / 2.0;
+
+    // minifb no puede capturar el cursor, así que se mira arrastrando con el
+    // botón izquierdo para no chocar con los bordes de la ventana
+    let mouse_pos = window.get_mouse_pos(MouseMode::Pass);
+    if window.get_mouse_down(MouseButton::Left) {
+        if let (Some((x, y)), Some((last_x, last_y))) = (mouse_pos, *last_mouse_pos) {
+            camera.look(-(x - last_x) * mouse_sensitivity, -(y - last_y) * mouse_sensitivity);
+        }
+    }
+    *last_mouse_pos = mouse_pos;
+
+    // La rueda cambia la velocidad máxima en pasos de un 20 %
+    if let Some((_, scroll)) = window.get_scroll_wheel() {
+        if scroll != 0.0 {
+            camera.fly_speed = (camera.fly_speed * 1.2f32.powf(scroll.signum())).clamp(0.1, 200.0);
+        }
+    }
+
+    let mut input = Vec3::new(0.0, 0.0, 0.0);
+    if window.is_key_down(Key::W) {
+        input.z -= 1.0;
+    }
+    if window.is_key_down(Key::S) {
+        input.z += 1.0;
+    }
+    if window.is_key_down(Key::D) {
+        input.x += 1.0;
+    }
+    if window.is_key_down(Key::A) {
+        input.x -= 1.0;
+    }
+    if window.is_key_down(Key::R) {
+        input.y += 1.0;
+    }
+    if window.is_key_down(Key::F) {
+        input.y -= 1.0;
+    }
+    camera.fly(input, delta_time);
+
+    if window.is_key_down(Key::Q) {
+        camera.roll(-roll_speed * delta_time);
+    }
+    if window.is_key_down(Key::E) {
+        camera.roll(roll_speed * delta_time);
+    }
+}
+
+// Teclas numéricas para el Sol y los cuerpos que lo orbitan directamente, en
+// el orden de `BODIES`, y Tab para recorrerlos todos
+const FOCUS_KEYS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+fn handle_focus_input(window: &Window, current: Option<usize>, body_count: usize) -> Option<usize> {
+    if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+        return Some(current.map_or(0, |index| (index + 1) % body_count));
+    }
+
+    let primaries = (0..BODY_COUNT).filter(|&index| is_primary(index));
+    FOCUS_KEYS
+        .iter()
+        .zip(primaries)
+        .find(|(key, _)| window.is_key_pressed(**key, KeyRepeat::No))
+        .map(|(_, index)| index)
+}
+
+// Cambia el modo de cámara conservando el ojo y el centro. Al volver a la órbita
+// se nivela el horizonte, que la órbita asume en el eje Y; el planetario guarda
+// la vista anterior en `saved_view` y la restaura al salir.
+fn set_camera_mode(
+    camera: &mut Camera,
+    mode: &mut CameraMode,
+    new_mode: CameraMode,
+    saved_view: &mut (Vec3, Vec3),
+) {
+    if *mode == new_mode {
+        return;
+    }
+
+    if *mode == CameraMode::Orrery {
+        let (offset, up) = *saved_view;
+        camera.eye = camera.center + offset;
+        camera.up = up;
+        camera.projection = Projection::Perspective;
+    }
+    if new_mode == CameraMode::Orbit {
+        camera.up = Vec3::new(0.0, 1.0, 0.0);
+    }
+    if new_mode == CameraMode::Orrery {
+        *saved_view = (camera.eye - camera.center, camera.up);
+        camera.eye = camera.center + Vec3::new(0.0, ORRERY_HEIGHT, 0.0);
+        camera.up = Vec3::new(0.0, 0.0, -1.0);
+        camera.projection = Projection::Orthographic;
+    }
+
+    camera.velocity = Vec3::new(0.0, 0.0, 0.0);
+    camera.has_changed = true;
+    *mode = new_mode;
+}
+
+fn handle_orrery_input(window: &Window, camera: &mut Camera, delta_time: f32) {
+    // El desplazamiento se ajusta a lo que se ve para que el zoom no lo cambie
+    let pan_speed = camera.ortho_height * 0.5 * delta_time;
+    let zoom_factor = 1.0 + 1.5 * delta_time;
+
+    let mut pan = Vec3::new(0.0, 0.0, 0.0);
+    if window.is_key_down(Key::W) {
+        pan.z -= pan_speed;
+    }
+    if window.is_key_down(Key::S) {
+        pan.z += pan_speed;
+    }
+    if window.is_key_down(Key::A) {
+        pan.x -= pan_speed;
+    }
+    if window.is_key_down(Key::D) {
+        pan.x += pan_speed;
+    }
+    camera.eye += pan;
+    camera.center += pan;
+
+    if window.is_key_down(Key::Up) {
+        camera.ortho_height = (camera.ortho_height / zoom_factor).max(1.0);
+    }
+    if window.is_key_down(Key::Down) {
+        camera.ortho_height = (camera.ortho_height * zoom_factor).min(500.0);
+    }
+    if let Some((_, scroll)) = window.get_scroll_wheel() {
+        if scroll != 0.0 {
+            camera.ortho_height = (camera.ortho_height * 1.2f32.powf(-scroll.signum())).clamp(1.0, 500.0);
+        }
+    }
+    camera.has_changed = true;
+}
+
+// Cuerpo bajo el cursor y su distancia a la cámara. `size` es el tamaño del
+// framebuffer, que puede no coincidir con el de la ventana.
+fn pick_body(
+    window: &Window,
+    camera: &Camera,
+    bodies: &[(&str, Vec3, f32)],
+    size: (f32, f32),
+) -> Option<(usize, f32)> {
+    let (x, y) = window.get_mouse_pos(MouseMode::Discard)?;
+    let (window_width, window_height) = window.get_size();
+    let (width, height) = size;
+
+    let ray = Ray::from_screen(
+        x * width / window_width as f32,
+        y * height / window_height as f32,
+        width,
+        height,
+        &create_view_matrix(camera.eye, camera.center, camera.up),
+        &camera.projection_matrix(width, height),
+    )?;
+
+    pick(
+        &ray,
+        bodies
+            .iter()
+            .map(|&(_, position, scale)| (position, SPHERE_RADIUS * scale)),
+    )
+}
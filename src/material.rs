@@ -0,0 +1,61 @@
+use nalgebra_glm::Vec3;
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub emissive: Vec3,
+    pub shininess: f32,
+    pub opacity: f32,
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+}
+
+impl Material {
+    // Converts a material parsed from an MTL file. Texture paths in the MTL are
+    // relative to the file itself, so they get resolved against `base_dir`.
+    pub fn from_mtl(material: &tobj::Material, base_dir: &Path) -> Self {
+        let default = Material::default();
+        let to_vec3 = |c: Option<[f32; 3]>, fallback: Vec3| {
+            c.map(|c| Vec3::new(c[0], c[1], c[2])).unwrap_or(fallback)
+        };
+        let resolve = |path: &Option<String>| {
+            path.as_ref()
+                .map(|p| base_dir.join(p).to_string_lossy().into_owned())
+        };
+
+        Material {
+            name: material.name.clone(),
+            ambient: to_vec3(material.ambient, default.ambient),
+            diffuse: to_vec3(material.diffuse, default.diffuse),
+            specular: to_vec3(material.specular, default.specular),
+            emissive: to_vec3(material.emissive, default.emissive),
+            shininess: material.shininess.unwrap_or(default.shininess),
+            opacity: material.dissolve.unwrap_or(default.opacity),
+            diffuse_map: resolve(&material.diffuse_texture),
+            specular_map: resolve(&material.specular_texture),
+            normal_map: resolve(&material.normal_texture),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::from("default"),
+            ambient: Vec3::new(1.0, 1.0, 1.0),
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            emissive: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 1.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::color::Color;
+use crate::texture::equirectangular_uv;
+use crate::vertex::Vertex;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+
+// Indexed triangle mesh. Meshes are shared between bodies through `Rc<Mesh>`,
+// so the same sphere is loaded once and drawn with different uniforms.
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+// One copy of a mesh in an instanced draw: its model matrix, applied after
+// the draw's own, and a tint multiplied into whatever the shader returns
+#[derive(Clone, Copy, Debug)]
+pub struct Instance {
+    pub model_matrix: Mat4,
+    pub tint: Color,
+    // Added to the draw's `Uniforms::object_id`; stays the same from frame to
+    // frame for the same copy
+    pub id: u32,
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Mesh { vertices, indices }
+    }
+
+    pub fn triangle_indices(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0] as usize, tri[1] as usize, tri[2] as usize])
+    }
+
+    // Replaces the uvs with the equirectangular projection of each vertex
+    // direction, for sphere models whose authored uvs are unusable. Triangles
+    // that straddle the u = 0/1 seam get their low-u vertices duplicated with
+    // u + 1, which samples correctly with repeat wrapping.
+    pub fn with_spherical_uvs(&self) -> Mesh {
+        let mut vertices: Vec<Vertex> = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let mut vertex = vertex.clone();
+                vertex.tex_coords = equirectangular_uv(&vertex.position);
+                vertex
+            })
+            .collect();
+        let mut indices = self.indices.clone();
+        let mut seam_copies: HashMap<u32, u32> = HashMap::new();
+
+        for tri in indices.chunks_exact_mut(3) {
+            let us = [tri[0], tri[1], tri[2]].map(|index| vertices[index as usize].tex_coords.x);
+            let min_u = us.iter().cloned().fold(f32::INFINITY, f32::min);
+            let max_u = us.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            if max_u - min_u <= 0.5 {
+                continue;
+            }
+
+            for (index, u) in tri.iter_mut().zip(us) {
+                if u < 0.5 {
+                    let original = *index;
+                    *index = *seam_copies.entry(original).or_insert_with(|| {
+                        let mut copy = vertices[original as usize].clone();
+                        copy.tex_coords.x += 1.0;
+                        vertices.push(copy);
+                        (vertices.len() - 1) as u32
+                    });
+                }
+            }
+        }
+
+        let mut mesh = Mesh::new(vertices, indices);
+        mesh.generate_tangents();
+        mesh
+    }
+
+    // Splits every triangle into four, `levels` times. New vertices are pushed
+    // out to the sphere through their edge so the mesh stays round, which is
+    // what displaced planets need to show detail. Edge midpoints are shared
+    // between the two triangles of the edge.
+    pub fn subdivided_sphere(&self, levels: usize) -> Mesh {
+        let mut vertices = self.vertices.clone();
+        let mut indices = self.indices.clone();
+
+        for _ in 0..levels {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut next_indices = Vec::with_capacity(indices.len() * 4);
+
+            let mut midpoint = |a: u32, b: u32, vertices: &mut Vec<Vertex>| {
+                let key = (a.min(b), a.max(b));
+                *midpoints.entry(key).or_insert_with(|| {
+                    let (va, vb) = (&vertices[a as usize], &vertices[b as usize]);
+                    let radius = (va.position.magnitude() + vb.position.magnitude()) * 0.5;
+                    let direction = (va.position + vb.position).normalize();
+
+                    let mut vertex = Vertex::new(
+                        direction * radius,
+                        (va.normal + vb.normal).normalize(),
+                        (va.tex_coords + vb.tex_coords) * 0.5,
+                    );
+                    vertex.color = va.color;
+                    vertices.push(vertex);
+                    (vertices.len() - 1) as u32
+                })
+            };
+
+            for tri in indices.chunks_exact(3) {
+                let (a, b, c) = (tri[0], tri[1], tri[2]);
+                let ab = midpoint(a, b, &mut vertices);
+                let bc = midpoint(b, c, &mut vertices);
+                let ca = midpoint(c, a, &mut vertices);
+
+                next_indices.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+            }
+
+            indices = next_indices;
+        }
+
+        let mut mesh = Mesh::new(vertices, indices);
+        mesh.generate_tangents();
+        mesh
+    }
+
+    // Smooth normals from the faces around each vertex, weighted by area, for
+    // meshes whose vertices were moved. Vertices at the same position (uv
+    // seam copies) share their normal so the seam does not show in the
+    // shading.
+    pub fn recompute_normals(&mut self) {
+        let key = |position: &Vec3| [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()];
+        let mut normals: HashMap<[u32; 3], Vec3> = HashMap::new();
+
+        for [i1, i2, i3] in self.triangle_indices() {
+            let (a, b, c) = (
+                self.vertices[i1].position,
+                self.vertices[i2].position,
+                self.vertices[i3].position,
+            );
+            // Twice the triangle's area, counter-clockwise faces point outwards
+            let face_normal = (b - a).cross(&(c - a));
+            for position in [a, b, c] {
+                *normals.entry(key(&position)).or_insert_with(Vec3::zeros) += face_normal;
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            if let Some(normal) = normals.get(&key(&vertex.position)) {
+                if normal.magnitude() > 1e-12 {
+                    vertex.normal = normal.normalize();
+                }
+            }
+        }
+        self.generate_tangents();
+    }
+
+    // Per-vertex tangents from the uv layout: each triangle adds its dP/du and
+    // dP/dv to its vertices, then the sum is orthogonalized against the normal.
+    // Vertices without usable uvs get an arbitrary tangent perpendicular to the
+    // normal.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vec3::zeros(); self.vertices.len()];
+        let mut bitangents = vec![Vec3::zeros(); self.vertices.len()];
+
+        for [i1, i2, i3] in self.triangle_indices() {
+            let (v1, v2, v3) = (&self.vertices[i1], &self.vertices[i2], &self.vertices[i3]);
+            let e1 = v2.position - v1.position;
+            let e2 = v3.position - v1.position;
+            let d1 = v2.tex_coords - v1.tex_coords;
+            let d2 = v3.tex_coords - v1.tex_coords;
+
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let r = 1.0 / det;
+            let tangent = (e1 * d2.y - e2 * d1.y) * r;
+            let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+
+            for index in [i1, i2, i3] {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        for ((vertex, tangent), bitangent) in self.vertices.iter_mut().zip(tangents).zip(bitangents) {
+            let normal = vertex.normal.normalize();
+            let mut t = tangent - normal * normal.dot(&tangent);
+            if t.magnitude() < 1e-8 {
+                // Any axis that is not parallel to the normal
+                let axis = if normal.x.abs() < 0.9 { Vec3::x() } else { Vec3::y() };
+                t = axis - normal * normal.dot(&axis);
+            }
+            let t = t.normalize();
+            let handedness = if normal.cross(&t).dot(&bitangent) < 0.0 { -1.0 } else { 1.0 };
+
+            vertex.tangent = Vec4::new(t.x, t.y, t.z, handedness);
+        }
+    }
+}
+
+// Post-transform vertex cache. Every vertex of the mesh has a slot that is
+// filled the first time an index references it, so shared vertices go through
+// the vertex shader exactly once per draw.
+pub struct VertexCache<'a, F>
+where
+    F: Fn(&Vertex) -> Vertex,
+{
+    mesh: &'a Mesh,
+    transform: F,
+    slots: Vec<Option<Vertex>>,
+}
+
+impl<'a, F> VertexCache<'a, F>
+where
+    F: Fn(&Vertex) -> Vertex,
+{
+    pub fn new(mesh: &'a Mesh, transform: F) -> Self {
+        VertexCache {
+            mesh,
+            transform,
+            slots: vec![None; mesh.vertices.len()],
+        }
+    }
+
+    pub fn fetch_triangle(&mut self, indices: [usize; 3]) -> [&Vertex; 3] {
+        for &index in &indices {
+            if self.slots[index].is_none() {
+                self.slots[index] = Some((self.transform)(&self.mesh.vertices[index]));
+            }
+        }
+
+        indices.map(|index| self.slots[index].as_ref().unwrap())
+    }
+}
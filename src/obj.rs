@@ -1,29 +1,46 @@
 use tobj;
 use nalgebra_glm::{Vec2, Vec3};
+use std::path::Path;
+use std::rc::Rc;
+use crate::material::Material;
+use crate::mesh::Mesh;
 use crate::vertex::Vertex;
 
 pub struct Obj {
-    meshes: Vec<Mesh>,
+    meshes: Vec<ObjMesh>,
+    materials: Vec<Material>,
 }
 
-struct Mesh {
+struct ObjMesh {
     vertices: Vec<Vec3>,
     normals: Vec<Vec3>,
     texcoords: Vec<Vec2>,
     indices: Vec<u32>,
+    material_id: Option<usize>,
 }
 
 impl Obj {
     pub fn load(filename: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _) = tobj::load_obj(filename, &tobj::LoadOptions {
+        let (models, materials) = tobj::load_obj(filename, &tobj::LoadOptions {
             single_index: true,
             triangulate: true,
             ..Default::default()
         })?;
 
+        // A missing or broken MTL file is not fatal, the meshes just render
+        // with the default material
+        let base_dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        let materials = materials
+            .map(|materials| {
+                materials.iter()
+                    .map(|material| Material::from_mtl(material, base_dir))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         let meshes = models.into_iter().map(|model| {
             let mesh = model.mesh;
-            Mesh {
+            ObjMesh {
                 vertices: mesh.positions.chunks(3)
                     .map(|v| Vec3::new(v[0], v[1], v[2]))
                     .collect(),
@@ -34,29 +51,73 @@ impl Obj {
                     .map(|t| Vec2::new(t[0], 1.0 - t[1]))
                     .collect(),
                 indices: mesh.indices,
+                material_id: mesh.material_id,
             }
         }).collect();
 
-        Ok(Obj { meshes })
+        Ok(Obj { meshes, materials })
     }
 
-    pub fn get_vertex_array(&self) -> Vec<Vertex> {
+    // Merges every mesh in the file into a single indexed mesh
+    pub fn get_mesh(&self) -> Rc<Mesh> {
         let mut vertices = Vec::new();
+        let mut indices = Vec::new();
 
         for mesh in &self.meshes {
-            for &index in &mesh.indices {
-                let position = mesh.vertices[index as usize];
-                let normal = mesh.normals.get(index as usize)
-                    .cloned()
-                    .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
-                let tex_coords = mesh.texcoords.get(index as usize)
-                    .cloned()
-                    .unwrap_or(Vec2::new(0.0, 0.0));
-
-                vertices.push(Vertex::new(position, normal, tex_coords));
-            }
+            let offset = vertices.len() as u32;
+            vertices.extend(Self::mesh_vertices(mesh));
+            indices.extend(mesh.indices.iter().map(|&index| index + offset));
+        }
+
+        let mut mesh = Mesh::new(vertices, indices);
+        mesh.generate_tangents();
+        Rc::new(mesh)
+    }
+
+    // One indexed mesh per material, so each can be drawn with its own
+    // material. Meshes without a valid material id get the default material.
+    pub fn get_material_meshes(&self) -> Vec<(Rc<Mesh>, Material)> {
+        let mut groups: Vec<(Option<usize>, Vec<Vertex>, Vec<u32>)> = Vec::new();
+
+        for mesh in &self.meshes {
+            let material_id = mesh.material_id.filter(|&id| id < self.materials.len());
+            let position = match groups.iter().position(|(id, _, _)| *id == material_id) {
+                Some(position) => position,
+                None => {
+                    groups.push((material_id, Vec::new(), Vec::new()));
+                    groups.len() - 1
+                }
+            };
+
+            let (_, vertices, indices) = &mut groups[position];
+            let offset = vertices.len() as u32;
+            vertices.extend(Self::mesh_vertices(mesh));
+            indices.extend(mesh.indices.iter().map(|&index| index + offset));
         }
 
-        vertices
+        groups.into_iter()
+            .map(|(material_id, vertices, indices)| {
+                let material = material_id
+                    .map(|id| self.materials[id].clone())
+                    .unwrap_or_default();
+                let mut mesh = Mesh::new(vertices, indices);
+                mesh.generate_tangents();
+                (Rc::new(mesh), material)
+            })
+            .collect()
+    }
+
+    // Unique vertices of the mesh, addressed by its index buffer
+    fn mesh_vertices(mesh: &ObjMesh) -> Vec<Vertex> {
+        mesh.vertices.iter().enumerate().map(|(index, &position)| {
+            let normal = mesh.normals.get(index)
+                .cloned()
+                .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+            let tex_coords = mesh.texcoords.get(index)
+                .cloned()
+                .unwrap_or(Vec2::new(0.0, 0.0));
+
+            Vertex::new(position, normal, tex_coords)
+        }).collect()
     }
 }
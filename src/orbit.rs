@@ -0,0 +1,215 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::line::{line, LineStyle};
+use crate::Uniforms;
+use nalgebra_glm::{DVec3, Vec3};
+use std::f32::consts::PI;
+
+const SEGMENTS: usize = 128;
+// Opacity of the trail just ahead of the body, the faintest part
+const TRAIL_MIN_ALPHA: f32 = 0.15;
+
+// Closed path of a body around its parent, as points starting at the body and
+// following its motion
+pub struct Orbit {
+    points: Vec<Vec3>,
+}
+
+impl Orbit {
+    // Circle through the body. `axis` is the orbit normal, with the body
+    // moving counter-clockwise around it.
+    pub fn around(parent: Vec3, body: Vec3, axis: Vec3) -> Option<Orbit> {
+        let offset = body - parent;
+        let radius = offset.magnitude();
+        if radius < 1e-4 {
+            return None;
+        }
+
+        // Unit vectors spanning the orbit plane: `start` points at the body
+        // and `forward` along its motion
+        let start = offset / radius;
+        let forward = axis.normalize().cross(&start).normalize();
+
+        Some(Orbit::traced(|turn| {
+            let angle = turn * 2.0 * PI;
+            parent + (start * angle.cos() + forward * angle.sin()) * radius
+        }))
+    }
+
+    // Path sampled from `point`, which gives the world position some share of
+    // a revolution ahead of the body, 0 being the body itself
+    pub fn traced<F: Fn(f32) -> Vec3>(point: F) -> Orbit {
+        Orbit {
+            points: (0..SEGMENTS).map(|segment| point(segment as f32 / SEGMENTS as f32)).collect(),
+        }
+    }
+}
+
+// Elliptic orbit around a focus, in scene units
+pub struct KeplerOrbit {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    // Longitude of the ascending node and argument of periapsis, in radians
+    pub ascending_node: f32,
+    pub periapsis_argument: f32,
+    // Mean anomaly at time 0, and how fast it grows per unit of `Uniforms::time`
+    pub mean_anomaly: f32,
+    pub mean_motion: f32,
+}
+
+impl KeplerOrbit {
+    // Offset from the focus at `time`, see `kepler_offset`
+    pub fn position(&self, time: f32) -> Vec3 {
+        let mean_anomaly = (self.mean_anomaly + self.mean_motion * time).rem_euclid(2.0 * PI);
+        let eccentric_anomaly = eccentric_anomaly(mean_anomaly as f64, self.eccentricity as f64);
+        let offset = kepler_offset(
+            self.semi_major_axis as f64,
+            self.eccentricity as f64,
+            self.inclination as f64,
+            self.ascending_node as f64,
+            self.periapsis_argument as f64,
+            eccentric_anomaly,
+        );
+        Vec3::new(offset.x as f32, offset.y as f32, offset.z as f32)
+    }
+
+    // Velocity at `time`, in scene units per unit of `Uniforms::time`
+    pub fn velocity(&self, time: f32) -> Vec3 {
+        // Central difference over a thousandth of a radian of mean anomaly
+        let step = 1e-3 / self.mean_motion.abs().max(1e-9);
+        (self.position(time + step) - self.position(time - step)) / (2.0 * step)
+    }
+
+    // Gravitational parameter (G times the focus' mass) these elements imply,
+    // from Kepler's third law: n² a³ = GM
+    pub fn gravitational_parameter(&self) -> f32 {
+        self.mean_motion * self.mean_motion * self.semi_major_axis.powi(3)
+    }
+}
+
+// Offset from the focus of a body at `eccentric_anomaly` on an orbit with
+// these elements, angles in radians. The reference plane is the scene's XZ
+// plane with +Y as its north, and prograde orbits run counter-clockwise seen
+// from above, like catalog::equatorial_direction's ecliptic. In f64 for
+// `bodies::Layout`: a tenth of a second of Sedna's orbit is far below what
+// f32 can tell apart.
+pub fn kepler_offset(
+    semi_major_axis: f64,
+    eccentricity: f64,
+    inclination: f64,
+    ascending_node: f64,
+    periapsis_argument: f64,
+    eccentric_anomaly: f64,
+) -> DVec3 {
+    let (a, e) = (semi_major_axis, eccentricity);
+
+    // In the orbit plane, x towards periapsis and y along the motion
+    let x = a * (eccentric_anomaly.cos() - e);
+    let y = a * (1.0 - e * e).sqrt() * eccentric_anomaly.sin();
+
+    let (sin_w, cos_w) = periapsis_argument.sin_cos();
+    let (sin_i, cos_i) = inclination.sin_cos();
+    let (sin_n, cos_n) = ascending_node.sin_cos();
+    let ecliptic = DVec3::new(
+        (cos_n * cos_w - sin_n * sin_w * cos_i) * x - (cos_n * sin_w + sin_n * cos_w * cos_i) * y,
+        (sin_n * cos_w + cos_n * sin_w * cos_i) * x - (sin_n * sin_w - cos_n * cos_w * cos_i) * y,
+        sin_w * sin_i * x + cos_w * sin_i * y,
+    );
+
+    // Ecliptic x, y, z to scene X, -Z, Y
+    DVec3::new(ecliptic.x, ecliptic.z, -ecliptic.y)
+}
+
+// Solves Kepler's equation M = E - e sin E for the eccentric anomaly E
+pub fn eccentric_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let mut anomaly = if eccentricity < 0.8 { mean_anomaly } else { std::f64::consts::PI };
+    for _ in 0..16 {
+        let step = (anomaly - eccentricity * anomaly.sin() - mean_anomaly) / (1.0 - eccentricity * anomaly.cos());
+        anomaly -= step;
+        if step.abs() < 1e-12 {
+            break;
+        }
+    }
+    anomaly
+}
+
+// Draws the orbit as a trail: brightest right behind the body and fading
+// around the path, and fainter the further it is from the camera.
+// `uniforms` should carry an identity model matrix.
+pub fn draw_orbit(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    orbit: &Orbit,
+    camera_position: &Vec3,
+    fade_distance: f32,
+    color: &Color,
+    style: LineStyle,
+) {
+    let count = orbit.points.len();
+
+    for (segment, &a) in orbit.points.iter().enumerate() {
+        let b = orbit.points[(segment + 1) % count];
+
+        let trail = TRAIL_MIN_ALPHA + (1.0 - TRAIL_MIN_ALPHA) * (segment as f32 / count as f32);
+        let distance = ((a + b) * 0.5 - camera_position).magnitude();
+        let fade = (1.0 - distance / fade_distance).clamp(0.0, 1.0);
+
+        let alpha = trail * fade;
+        if alpha > 0.01 {
+            line(framebuffer, uniforms, &a, &b, color, alpha, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    // Largest error in Kepler's equation over mean anomalies around the orbit
+    fn kepler_residual(eccentricity: f64) -> f64 {
+        (0..=1000)
+            .map(|step| {
+                let mean_anomaly = TAU * step as f64 / 1000.0;
+                let anomaly = eccentric_anomaly(mean_anomaly, eccentricity);
+                (anomaly - eccentricity * anomaly.sin() - mean_anomaly).abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn eccentric_anomaly_solves_keplers_equation() {
+        for eccentricity in [0.0, 0.0167, 0.2488, 0.436, 0.79, 0.8] {
+            assert!(kepler_residual(eccentricity) < 1e-10, "e = {}", eccentricity);
+        }
+    }
+
+    #[test]
+    fn eccentric_anomaly_converges_on_sednas_orbit() {
+        assert!(kepler_residual(0.8496) < 1e-10);
+        assert!(kepler_residual(0.95) < 1e-10);
+    }
+
+    #[test]
+    fn kepler_offset_spans_periapsis_to_apoapsis() {
+        let (a, e) = (10.0, 0.5);
+        let periapsis = kepler_offset(a, e, 0.3, 1.2, 2.0, 0.0);
+        let apoapsis = kepler_offset(a, e, 0.3, 1.2, 2.0, std::f64::consts::PI);
+
+        assert!((periapsis.magnitude() - a * (1.0 - e)).abs() < 1e-9);
+        assert!((apoapsis.magnitude() - a * (1.0 + e)).abs() < 1e-9);
+        // The focus lies between them on the major axis
+        assert!((periapsis.normalize() + apoapsis.normalize()).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn kepler_offset_keeps_flat_orbits_in_the_xz_plane() {
+        let offset = kepler_offset(1.0, 0.1, 0.0, 0.7, 0.4, 1.0);
+        assert!(offset.y.abs() < 1e-12);
+
+        // Prograde motion runs counter-clockwise seen from +Y
+        let later = kepler_offset(1.0, 0.1, 0.0, 0.7, 0.4, 1.1);
+        assert!(offset.cross(&later).y > 0.0);
+    }
+}
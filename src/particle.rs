@@ -0,0 +1,190 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::sphere::projected_world_radius;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+use rand::prelude::*;
+use std::f32::consts::PI;
+
+// Billboards never grow past this radius in pixels, so a camera flying
+// through a tail does not fill the screen with one particle
+const MAX_BILLBOARD_PIXELS: f32 = 48.0;
+// Share of its life a particle takes to fade in
+const FADE_IN: f32 = 0.1;
+
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    // Both in units of `Uniforms::time`
+    pub age: f32,
+    pub lifetime: f32,
+    // Random 0..1 picked at birth, for emitters that vary the physics per
+    // particle
+    pub variation: f32,
+}
+
+// How an emitter's particles look over their life
+#[derive(Clone, Copy)]
+pub struct ParticleSettings {
+    // Range lifetimes are picked from, in units of `Uniforms::time`
+    pub lifetime: (f32, f32),
+    // World-space radius and color at birth and at death
+    pub size: (f32, f32),
+    pub color: (Color, Color),
+    // Scales what each particle adds to the image, as they are blended
+    // additively and overlap heavily
+    pub brightness: f32,
+    // Particles alive at most; emission stops until some die
+    pub max_particles: usize,
+}
+
+pub struct ParticleSystem {
+    settings: ParticleSettings,
+    particles: Vec<Particle>,
+    rng: StdRng,
+    // Fraction of a particle owed from previous emissions
+    pending: f32,
+}
+
+impl ParticleSystem {
+    // The same seed always emits the same particles
+    pub fn new(settings: ParticleSettings, seed: u64) -> Self {
+        ParticleSystem {
+            settings,
+            particles: Vec::with_capacity(settings.max_particles),
+            rng: StdRng::seed_from_u64(seed),
+            pending: 0.0,
+        }
+    }
+
+    // Emits `rate` particles per unit of time over a step of `delta` that the
+    // existing particles were already moved through. Each one is born at a
+    // random moment of the step and `spawn` gives its position and velocity
+    // then, from how long after the start of the step that is; it is then
+    // moved for the rest of the step, so particles spread out along their
+    // path instead of bunching up once per step.
+    pub fn emit<F>(&mut self, rate: f32, delta: f32, mut spawn: F)
+    where
+        F: FnMut(&mut StdRng, f32) -> (Vec3, Vec3),
+    {
+        self.pending += rate * delta;
+        while self.pending >= 1.0 {
+            self.pending -= 1.0;
+            if self.particles.len() >= self.settings.max_particles {
+                continue;
+            }
+
+            let birth = self.rng.gen::<f32>() * delta;
+            let (position, velocity) = spawn(&mut self.rng, birth);
+            self.push(position, velocity, delta - birth);
+        }
+    }
+
+    // Emits `count` particles at once, at the end of the last step
+    pub fn burst<F>(&mut self, count: usize, mut spawn: F)
+    where
+        F: FnMut(&mut StdRng) -> (Vec3, Vec3),
+    {
+        for _ in 0..count {
+            if self.particles.len() >= self.settings.max_particles {
+                break;
+            }
+            let (position, velocity) = spawn(&mut self.rng);
+            self.push(position, velocity, 0.0);
+        }
+    }
+
+    fn push(&mut self, position: Vec3, velocity: Vec3, age: f32) {
+        let (min, max) = self.settings.lifetime;
+        self.particles.push(Particle {
+            position: position + velocity * age,
+            velocity,
+            age,
+            lifetime: self.rng.gen_range(min..max),
+            variation: self.rng.gen(),
+        });
+    }
+
+    // Ages and moves every particle by `delta`, accelerated by `acceleration`,
+    // and drops the ones whose life is over
+    pub fn update<F>(&mut self, delta: f32, acceleration: F)
+    where
+        F: Fn(&Particle) -> Vec3,
+    {
+        for particle in &mut self.particles {
+            particle.velocity += acceleration(particle) * delta;
+            particle.position += particle.velocity * delta;
+            particle.age += delta;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    // Camera-facing discs added onto the image and depth tested against it,
+    // without writing depth. `uniforms` should carry an identity model matrix.
+    pub fn render(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        let view_projection = uniforms.projection_matrix * uniforms.view_matrix;
+        let settings = &self.settings;
+
+        for particle in &self.particles {
+            let position = particle.position;
+            let clip = view_projection * Vec4::new(position.x, position.y, position.z, 1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let ndc = clip.xyz() / clip.w;
+            let depth = uniforms.depth.depth(ndc.z, clip.w);
+            if !(0.0..=1.0).contains(&depth) {
+                continue;
+            }
+            let screen = uniforms.viewport_matrix * Vec4::new(ndc.x, ndc.y, ndc.z, 1.0);
+
+            let life = (particle.age / particle.lifetime).clamp(0.0, 1.0);
+            let size = settings.size.0 + (settings.size.1 - settings.size.0) * life;
+            let pixels = projected_world_radius(uniforms, &position, size).min(MAX_BILLBOARD_PIXELS);
+            let fade = (life / FADE_IN).min(1.0) * (1.0 - life);
+            let color = settings.color.0.lerp(&settings.color.1, life) * (settings.brightness * fade);
+
+            draw_billboard(framebuffer, screen.x, screen.y, depth, pixels, &color);
+        }
+    }
+}
+
+// Steps to advance a simulation last brought up to `last` to `time`, as
+// (start, length) pairs no longer than `max_step`. A simulation that never
+// ran starts `warm_up` before `time`, so its particles are already out on the
+// first frame; going back in time gives no steps.
+pub fn time_steps(last: Option<f32>, time: f32, warm_up: f32, max_step: f32) -> Vec<(f32, f32)> {
+    let mut steps = Vec::new();
+    let mut current = last.unwrap_or(time - warm_up);
+    while current < time {
+        let delta = (time - current).min(max_step);
+        steps.push((current, delta));
+        current += delta;
+    }
+    steps
+}
+
+// Soft disc of `radius` pixels around a sub-pixel position, brightest in the
+// middle. Discs under a pixel put all the light they would have spread, πr²/3,
+// into the pixel they fall on, so distant particles do not flicker away.
+fn draw_billboard(framebuffer: &mut Framebuffer, x: f32, y: f32, depth: f32, radius: f32, color: &Color) {
+    if radius < 0.75 {
+        let coverage = (PI * radius * radius / 3.0).min(1.0);
+        framebuffer.add_point(x as i32, y as i32, depth, &(*color * coverage));
+        return;
+    }
+
+    let reach = radius.ceil() as i32;
+    let (center_x, center_y) = (x - 0.5, y - 0.5);
+    let (pixel_x, pixel_y) = (center_x.round() as i32, center_y.round() as i32);
+
+    for py in (pixel_y - reach)..=(pixel_y + reach) {
+        for px in (pixel_x - reach)..=(pixel_x + reach) {
+            let (dx, dy) = (px as f32 - center_x, py as f32 - center_y);
+            let falloff = 1.0 - (dx * dx + dy * dy) / (radius * radius);
+            if falloff > 0.0 {
+                framebuffer.add_point(px, py, depth, &(*color * (falloff * falloff)));
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+pub struct Ray {
+    pub origin: Vec3,
+    // Unit length
+    pub direction: Vec3,
+}
+
+impl Ray {
+    // Ray through a pixel, from the camera plane into the scene. Works for
+    // perspective and orthographic projections and every depth mode, since it
+    // only needs two points the inverse view-projection maps onto the line.
+    pub fn from_screen(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        view: &Mat4,
+        projection: &Mat4,
+    ) -> Option<Ray> {
+        let inverse = (projection * view).try_inverse()?;
+        let ndc_x = 2.0 * x / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height;
+
+        let unproject = |ndc_z: f32| {
+            let point = inverse * Vec4::new(ndc_x, ndc_y, ndc_z, 1.0);
+            point.xyz() / point.w
+        };
+        let a = unproject(0.5);
+        let b = unproject(0.9);
+
+        // The view looks down -Z, which `view` maps back to world space
+        let eye = inverse_view_position(view)?;
+        let forward = -Vec3::new(view[(2, 0)], view[(2, 1)], view[(2, 2)]);
+
+        let mut direction = (b - a).normalize();
+        if direction.dot(&forward) < 0.0 {
+            direction = -direction;
+        }
+
+        // Back along the ray to the plane of the eye: the eye itself for
+        // perspective rays, the pixel's spot on the image plane for orthographic
+        let origin = a - direction * (a - eye).dot(&forward) / direction.dot(&forward);
+
+        Some(Ray { origin, direction })
+    }
+
+    // Distance along the ray to the first hit with a sphere, if any is ahead
+    pub fn intersect_sphere(&self, center: &Vec3, radius: f32) -> Option<f32> {
+        let to_center = center - self.origin;
+        let along = to_center.dot(&self.direction);
+        let distance_squared = to_center.magnitude_squared() - along * along;
+        let radius_squared = radius * radius;
+        if distance_squared > radius_squared {
+            return None;
+        }
+
+        let half_chord = (radius_squared - distance_squared).sqrt();
+        let near = along - half_chord;
+        let far = along + half_chord;
+        if near >= 0.0 {
+            Some(near)
+        } else if far >= 0.0 {
+            // The origin is inside the sphere
+            Some(0.0)
+        } else {
+            None
+        }
+    }
+}
+
+// View directions through pixel centers for a whole frame. The inverse
+// view-projection is computed once, so this is cheap enough to run for every
+// pixel of the screen.
+pub struct ScreenRays {
+    inverse: Mat4,
+    eye: Vec3,
+    forward: Vec3,
+    width: f32,
+    height: f32,
+}
+
+impl ScreenRays {
+    pub fn new(width: usize, height: usize, view: &Mat4, projection: &Mat4) -> Option<ScreenRays> {
+        Some(ScreenRays {
+            inverse: (projection * view).try_inverse()?,
+            eye: inverse_view_position(view)?,
+            forward: -Vec3::new(view[(2, 0)], view[(2, 1)], view[(2, 2)]),
+            width: width as f32,
+            height: height as f32,
+        })
+    }
+
+    // Unit direction of the ray through the center of pixel (x, y)
+    pub fn direction(&self, x: usize, y: usize) -> Vec3 {
+        // Two points along the ray; which one is further depends on the depth
+        // mode, so the direction is flipped to face forward
+        let direction = (self.unproject(x, y, 0.9) - self.unproject(x, y, 0.5)).normalize();
+        if direction.dot(&self.forward) < 0.0 {
+            -direction
+        } else {
+            direction
+        }
+    }
+
+    // Ray through the center of pixel (x, y), starting on the plane of the
+    // eye like the ones from `Ray::from_screen`
+    pub fn ray(&self, x: usize, y: usize) -> Ray {
+        let direction = self.direction(x, y);
+        let point = self.unproject(x, y, 0.5);
+        let origin =
+            point - direction * (point - self.eye).dot(&self.forward) / direction.dot(&self.forward);
+        Ray { origin, direction }
+    }
+
+    fn unproject(&self, x: usize, y: usize, ndc_z: f32) -> Vec3 {
+        let ndc_x = 2.0 * (x as f32 + 0.5) / self.width - 1.0;
+        let ndc_y = 1.0 - 2.0 * (y as f32 + 0.5) / self.height;
+        let point = self.inverse * Vec4::new(ndc_x, ndc_y, ndc_z, 1.0);
+        point.xyz() / point.w
+    }
+}
+
+// Index and distance of the closest sphere the ray hits
+pub fn pick<I>(ray: &Ray, spheres: I) -> Option<(usize, f32)>
+where
+    I: IntoIterator<Item = (Vec3, f32)>,
+{
+    spheres
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, (center, radius))| {
+            ray.intersect_sphere(&center, radius).map(|t| (index, t))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+fn inverse_view_position(view: &Mat4) -> Option<Vec3> {
+    let inverse = view.try_inverse()?;
+    Some(Vec3::new(inverse[(0, 3)], inverse[(1, 3)], inverse[(2, 3)]))
+}
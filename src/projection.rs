@@ -0,0 +1,125 @@
+use nalgebra_glm::{ortho, perspective, Mat4};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // Parallel projection, used for the top-down orrery view
+    Orthographic,
+}
+
+// How view distance is turned into the value stored in the depth buffer.
+// All modes write 0..=1 for visible fragments and drop anything outside.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthMode {
+    // OpenGL-style hyperbolic depth, near = 0 and far = 1
+    Standard,
+    // Near = 1 and far = 0, so the fine float steps near zero land on the
+    // distant bodies that the hyperbolic curve squashes together
+    Reversed,
+    // Depth proportional to log(distance), even precision at every scale.
+    // Interpolated per vertex, so very large triangles can sort slightly off.
+    Logarithmic,
+}
+
+impl DepthMode {
+    pub fn next(self) -> Self {
+        match self {
+            DepthMode::Standard => DepthMode::Reversed,
+            DepthMode::Reversed => DepthMode::Logarithmic,
+            DepthMode::Logarithmic => DepthMode::Standard,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DepthMode::Standard => "estándar",
+            DepthMode::Reversed => "invertida",
+            DepthMode::Logarithmic => "logarítmica",
+        }
+    }
+}
+
+// What the vertex shader needs to write depth for a camera
+#[derive(Clone, Copy, Debug)]
+pub struct DepthRange {
+    pub mode: DepthMode,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for DepthRange {
+    fn default() -> Self {
+        DepthRange {
+            mode: DepthMode::Standard,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+impl DepthRange {
+    // Buffer depth of a vertex from its clip-space z / w and its w, which is
+    // the distance along the view direction for perspective projections
+    pub fn depth(&self, ndc_z: f32, w: f32) -> f32 {
+        match self.mode {
+            DepthMode::Standard => ndc_z * 0.5 + 0.5,
+            DepthMode::Reversed => ndc_z,
+            DepthMode::Logarithmic => {
+                if w <= 0.0 {
+                    return -1.0;
+                }
+                (w / self.near).ln() / (self.far / self.near).ln()
+            }
+        }
+    }
+
+    // Whether larger depth values are closer to the camera
+    pub fn is_reversed(&self) -> bool {
+        self.mode == DepthMode::Reversed
+    }
+
+    // Buffer depth of the far plane, where backdrops are drawn
+    pub fn farthest(&self) -> f32 {
+        if self.is_reversed() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+// `height` is the vertical extent of the orthographic view in world units
+pub fn projection_matrix(
+    projection: Projection,
+    depth: &DepthRange,
+    aspect_ratio: f32,
+    fov: f32,
+    height: f32,
+) -> Mat4 {
+    let (near, far) = (depth.near, depth.far);
+
+    match (projection, depth.mode) {
+        (Projection::Perspective, DepthMode::Reversed) => {
+            let focal = 1.0 / (fov / 2.0).tan();
+            let mut matrix = Mat4::zeros();
+            matrix[(0, 0)] = focal / aspect_ratio;
+            matrix[(1, 1)] = focal;
+            // z / w goes from 1 at the near plane to 0 at the far plane
+            matrix[(2, 2)] = near / (far - near);
+            matrix[(2, 3)] = far * near / (far - near);
+            matrix[(3, 2)] = -1.0;
+            matrix
+        }
+        (Projection::Perspective, _) => perspective(aspect_ratio, fov, near, far),
+        (Projection::Orthographic, mode) => {
+            let half_height = height / 2.0;
+            let half_width = half_height * aspect_ratio;
+            let mut matrix = ortho(-half_width, half_width, -half_height, half_height, near, far);
+            if mode == DepthMode::Reversed {
+                matrix[(2, 2)] = 1.0 / (far - near);
+                matrix[(2, 3)] = far / (far - near);
+            }
+            matrix
+        }
+    }
+}
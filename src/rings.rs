@@ -0,0 +1,476 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::picking::ScreenRays;
+use crate::Uniforms;
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use nalgebra_glm::{Vec3, Vec4};
+
+// Radial samples of the finest level of the profile
+const PROFILE_SAMPLES: usize = 4096;
+// Ringlets: fine structure within every band, as noise along the radius, in
+// cycles per planet radius
+const RINGLET_FREQUENCY: f32 = 60.0;
+const RINGLET_CONTRAST: f32 = 0.6;
+// Thickness of the ring slab in planet radii. Real rings are only tens of
+// meters thick; the slab is kept at least `MIN_THICKNESS_PIXELS` on screen so
+// seen edge-on the rings still show as an unbroken line.
+const THICKNESS: f32 = 0.004;
+const MIN_THICKNESS_PIXELS: f32 = 1.5;
+// Most samples taken along one pixel's ray through the slab
+const MAX_SAMPLES: usize = 48;
+// Henyey-Greenstein asymmetry of ice chunks, which scatter light mostly back
+// towards the Sun, and of fine dust, which scatters it forwards
+const ICE_ASYMMETRY: f32 = -0.2;
+const DUST_ASYMMETRY: f32 = 0.75;
+// Sunlight on the rings, so a thick ring facing both the Sun and the camera
+// shows about its own color
+const SUNLIGHT: f32 = 2.0;
+// Light reflected off the planet onto the night side and into the shadow
+const AMBIENT: f32 = 0.03;
+// Width of the edge of the planet's shadow, in planet radii
+const PENUMBRA: f32 = 0.02;
+
+// Part of a ring system between two radii, in planet radii
+pub struct RingBand {
+    pub inner: f32,
+    pub outer: f32,
+    // Normal optical depth at the inner and outer edge, linear in between
+    pub optical_depth: (f32, f32),
+    pub color: Color,
+    // Share of the light scattered by fine dust rather than by ice chunks
+    pub dust: f32,
+}
+
+// Saturn's main rings, from inside out, in radii of 60268 km. The Cassini
+// division between B and A is thin rather than empty; the Encke gap is.
+pub const SATURN_RINGS: [RingBand; 8] = [
+    // D
+    RingBand {
+        inner: 1.110,
+        outer: 1.236,
+        optical_depth: (0.001, 0.003),
+        color: Color::new(150, 135, 120),
+        dust: 0.9,
+    },
+    // C
+    RingBand {
+        inner: 1.239,
+        outer: 1.527,
+        optical_depth: (0.05, 0.2),
+        color: Color::new(160, 145, 125),
+        dust: 0.1,
+    },
+    // B, densest around its middle
+    RingBand {
+        inner: 1.527,
+        outer: 1.640,
+        optical_depth: (0.8, 1.4),
+        color: Color::new(230, 210, 175),
+        dust: 0.02,
+    },
+    RingBand {
+        inner: 1.640,
+        outer: 1.951,
+        optical_depth: (2.4, 1.8),
+        color: Color::new(230, 210, 175),
+        dust: 0.02,
+    },
+    // Cassini division
+    RingBand {
+        inner: 1.951,
+        outer: 2.027,
+        optical_depth: (0.08, 0.12),
+        color: Color::new(150, 140, 125),
+        dust: 0.2,
+    },
+    // A, split by the Encke gap
+    RingBand {
+        inner: 2.027,
+        outer: 2.214,
+        optical_depth: (0.6, 0.45),
+        color: Color::new(210, 195, 170),
+        dust: 0.05,
+    },
+    RingBand {
+        inner: 2.219,
+        outer: 2.269,
+        optical_depth: (0.4, 0.3),
+        color: Color::new(210, 195, 170),
+        dust: 0.05,
+    },
+    // F
+    RingBand {
+        inner: 2.320,
+        outer: 2.327,
+        optical_depth: (0.5, 0.5),
+        color: Color::new(220, 210, 195),
+        dust: 0.8,
+    },
+];
+
+// One texel of the profile. Color and dust are weighted by optical depth, so
+// averaging texels for the coarser levels keeps the denser material's look.
+#[derive(Clone, Copy)]
+struct ProfileSample {
+    optical_depth: f32,
+    color: Vec3,
+    dust: f32,
+}
+
+impl ProfileSample {
+    fn average(a: &ProfileSample, b: &ProfileSample) -> ProfileSample {
+        ProfileSample {
+            optical_depth: (a.optical_depth + b.optical_depth) * 0.5,
+            color: (a.color + b.color) * 0.5,
+            dust: (a.dust + b.dust) * 0.5,
+        }
+    }
+
+    fn lerp(&self, other: &ProfileSample, t: f32) -> ProfileSample {
+        ProfileSample {
+            optical_depth: self.optical_depth + (other.optical_depth - self.optical_depth) * t,
+            color: self.color.lerp(&other.color, t),
+            dust: self.dust + (other.dust - self.dust) * t,
+        }
+    }
+}
+
+// A planet's rings as a thin slab of particles around its equator. Every
+// pixel's view ray is followed through the slab, adding up the light the
+// particles scatter from the Sun and dimming what lies behind, so the rings
+// look right from above, from below and edge-on from one flat piece.
+pub struct RingSystem {
+    sun: Vec3,
+    inner: f32,
+    outer: f32,
+    // Radial profile from `inner` to `outer`, finest level first, each level
+    // half as long as the one before
+    levels: Vec<Vec<ProfileSample>>,
+}
+
+impl RingSystem {
+    // The same seed always gives the same ringlets
+    pub fn new(sun: Vec3, bands: &[RingBand], seed: i32) -> Self {
+        let inner = bands.iter().map(|band| band.inner).fold(f32::INFINITY, f32::min);
+        let outer = bands.iter().map(|band| band.outer).fold(0.0, f32::max);
+
+        let mut noise = FastNoiseLite::with_seed(seed);
+        noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        noise.set_frequency(Some(RINGLET_FREQUENCY));
+        noise.set_fractal_type(Some(FractalType::FBm));
+        noise.set_fractal_octaves(Some(4));
+
+        let texel = (outer - inner) / PROFILE_SAMPLES as f32;
+        let finest = (0..PROFILE_SAMPLES)
+            .map(|index| {
+                let radius = inner + (index as f32 + 0.5) * texel;
+                let Some(band) = bands.iter().find(|band| (band.inner..band.outer).contains(&radius)) else {
+                    return ProfileSample {
+                        optical_depth: 0.0,
+                        color: Vec3::zeros(),
+                        dust: 0.0,
+                    };
+                };
+                let across = (radius - band.inner) / (band.outer - band.inner);
+                let ringlets = (1.0 + RINGLET_CONTRAST * noise.get_noise_2d(radius, 0.0)).max(0.0);
+                let optical_depth =
+                    (band.optical_depth.0 + (band.optical_depth.1 - band.optical_depth.0) * across) * ringlets;
+                ProfileSample {
+                    optical_depth,
+                    color: band.color.to_vec3() * optical_depth,
+                    dust: band.dust * optical_depth,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut levels = vec![finest];
+        while let Some(last) = levels.last().filter(|level| level.len() > 1) {
+            let next = last
+                .chunks(2)
+                .map(|pair| ProfileSample::average(&pair[0], pair.last().unwrap()))
+                .collect();
+            levels.push(next);
+        }
+
+        RingSystem {
+            sun,
+            inner,
+            outer,
+            levels,
+        }
+    }
+
+    // Moves the light, for when the planet or the scene moves around the Sun
+    pub fn set_sun(&mut self, sun: Vec3) {
+        self.sun = sun;
+    }
+
+    // Level of the profile blurred over about `footprint` planet radii, so
+    // distant rings do not shimmer
+    fn level(&self, footprint: f32) -> usize {
+        let finest_texel = (self.outer - self.inner) / PROFILE_SAMPLES as f32;
+        ((footprint / finest_texel).log2().floor().max(0.0) as usize).min(self.levels.len() - 1)
+    }
+
+    // Profile at `radius` on `level`. Color and dust come back unweighted.
+    fn sample(&self, radius: f32, level: usize) -> ProfileSample {
+        let empty = ProfileSample {
+            optical_depth: 0.0,
+            color: Vec3::zeros(),
+            dust: 0.0,
+        };
+        if radius < self.inner || radius > self.outer {
+            return empty;
+        }
+
+        let texels = &self.levels[level];
+        let position = (radius - self.inner) / (self.outer - self.inner) * texels.len() as f32 - 0.5;
+        let index = (position.floor().max(0.0) as usize).min(texels.len() - 1);
+        let next = (index + 1).min(texels.len() - 1);
+        let sample = texels[index].lerp(&texels[next], position - position.floor());
+
+        if sample.optical_depth <= 0.0 {
+            return empty;
+        }
+        ProfileSample {
+            optical_depth: sample.optical_depth,
+            color: sample.color / sample.optical_depth,
+            dust: sample.dust / sample.optical_depth,
+        }
+    }
+
+    // Blends the rings over what is already drawn, hidden behind anything
+    // closer in the depth buffer, which they leave alone. `uniforms` should
+    // carry the planet's model matrix: its origin the planet's center, its XZ
+    // plane the ring plane and one unit one planet radius, scaled uniformly.
+    pub fn render(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        let model = uniforms.model_matrix;
+        let model_view = uniforms.view_matrix * model;
+        let Some(inverse_model_view) = model_view.try_inverse() else {
+            return;
+        };
+        // Rays straight into planet space, where everything below happens
+        let Some(rays) = ScreenRays::new(
+            framebuffer.width,
+            framebuffer.height,
+            &model_view,
+            &uniforms.projection_matrix,
+        ) else {
+            return;
+        };
+        let model_view_projection = uniforms.projection_matrix * model_view;
+
+        // Size of a pixel in planet radii, at a distance in planet radii
+        let planet_radius = (model * Vec4::new(1.0, 0.0, 0.0, 0.0)).xyz().magnitude();
+        let perspective = uniforms.projection_matrix[(3, 3)] == 0.0;
+        let pixel_angle = 2.0 / (uniforms.projection_matrix[(1, 1)] * framebuffer.height as f32);
+        let pixel_size = |distance: f32| {
+            if perspective {
+                pixel_angle * distance
+            } else {
+                pixel_angle / planet_radius
+            }
+        };
+
+        let eye = Vec3::new(inverse_model_view[(0, 3)], inverse_model_view[(1, 3)], inverse_model_view[(2, 3)]);
+        let thickness = THICKNESS.max(MIN_THICKNESS_PIXELS * pixel_size(eye.magnitude()));
+        let sun = match model.try_inverse() {
+            Some(inverse_model) => (inverse_model * Vec4::new(self.sun.x, self.sun.y, self.sun.z, 1.0)).xyz(),
+            None => return,
+        };
+
+        let Some((min_x, min_y, max_x, max_y)) =
+            self.screen_bounds(uniforms, framebuffer.width, framebuffer.height, thickness)
+        else {
+            return;
+        };
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let ray = rays.ray(x, y);
+                let (origin, direction) = (ray.origin, ray.direction);
+                let Some((near, far)) = self.slab_span(&origin, &direction, thickness) else {
+                    continue;
+                };
+
+                let footprint = pixel_size(near);
+                let level = self.level(footprint);
+                let across = direction.xz().magnitude() * (far - near);
+                let samples = ((across / footprint).ceil() as usize).clamp(1, MAX_SAMPLES);
+                let step = (far - near) / samples as f32;
+
+                let mut light = Vec3::zeros();
+                let mut transmittance = 1.0;
+                let mut front_depth = None;
+
+                for index in 0..samples {
+                    let point = origin + direction * (near + (index as f32 + 0.5) * step);
+
+                    // Nothing past the first opaque surface shows
+                    let clip = model_view_projection * Vec4::new(point.x, point.y, point.z, 1.0);
+                    if clip.w <= 0.0 {
+                        continue;
+                    }
+                    let depth = uniforms.depth.depth(clip.z / clip.w, clip.w);
+                    if !(0.0..=1.0).contains(&depth) || !framebuffer.depth_test(x, y, depth) {
+                        break;
+                    }
+
+                    let particles = self.sample(point.xz().magnitude(), level);
+                    if particles.optical_depth <= 0.0 {
+                        continue;
+                    }
+                    front_depth.get_or_insert(depth);
+
+                    let extinction = particles.optical_depth / thickness;
+                    let step_depth = extinction * step;
+                    let source = self.scattered_light(&point, &direction, &sun, &particles, extinction, thickness);
+
+                    let absorbed = 1.0 - (-step_depth).exp();
+                    light += source * (transmittance * absorbed);
+                    transmittance *= 1.0 - absorbed;
+                    if transmittance < 1.0 / 255.0 {
+                        break;
+                    }
+                }
+
+                let alpha = 1.0 - transmittance;
+                let Some(depth) = front_depth.filter(|_| alpha > 1.0 / 255.0) else {
+                    continue;
+                };
+                // `blend_point` mixes by alpha, while the slab adds its light
+                // on top of what it lets through
+                let color = light / alpha;
+                framebuffer.blend_point(
+                    x as i32,
+                    y as i32,
+                    depth,
+                    &Color::from_float(color.x, color.y, color.z),
+                    alpha,
+                );
+            }
+        }
+    }
+
+    // Light one unit of optical depth at `point` sends along the view ray:
+    // sunlight that made it through the particles between it and the lit face
+    // of the slab, unless the planet is in the way, scattered by the phase
+    // function of ice and dust, plus a little ambient light.
+    fn scattered_light(
+        &self,
+        point: &Vec3,
+        view_direction: &Vec3,
+        sun: &Vec3,
+        particles: &ProfileSample,
+        extinction: f32,
+        thickness: f32,
+    ) -> Vec3 {
+        let to_sun = (sun - point).normalize();
+
+        let to_lit_face = if to_sun.y > 0.0 {
+            thickness * 0.5 - point.y
+        } else {
+            point.y + thickness * 0.5
+        };
+        let sun_depth = extinction * to_lit_face.max(0.0) / to_sun.y.abs().max(1e-3);
+
+        // Closest approach of the ray to the Sun to the planet's center
+        let along = -point.dot(&to_sun);
+        let lit = if along > 0.0 {
+            let miss = (point + to_sun * along).magnitude();
+            ((miss - 1.0) / PENUMBRA + 0.5).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        // Angle between the sunlight's path and the way it leaves to the
+        // camera: 1 looking into the Sun, -1 with the Sun behind
+        let cos_angle = to_sun.dot(view_direction);
+        let phase = (1.0 - particles.dust) * henyey_greenstein(ICE_ASYMMETRY, cos_angle)
+            + particles.dust * henyey_greenstein(DUST_ASYMMETRY, cos_angle);
+
+        particles.color * (SUNLIGHT * lit * phase * (-sun_depth).exp() + AMBIENT)
+    }
+
+    // Distances along the ray where it enters and leaves the part of the slab
+    // within the outer edge, if it crosses it ahead of its origin
+    fn slab_span(&self, origin: &Vec3, direction: &Vec3, thickness: f32) -> Option<(f32, f32)> {
+        let half = thickness * 0.5;
+        let (mut near, mut far) = if direction.y.abs() < 1e-9 {
+            if origin.y.abs() > half {
+                return None;
+            }
+            (0.0f32, f32::INFINITY)
+        } else {
+            let a = (-half - origin.y) / direction.y;
+            let b = (half - origin.y) / direction.y;
+            (a.min(b), a.max(b))
+        };
+
+        // Cylinder around the axis at the outer edge
+        let a = direction.x * direction.x + direction.z * direction.z;
+        let b = 2.0 * (origin.x * direction.x + origin.z * direction.z);
+        let c = origin.x * origin.x + origin.z * origin.z - self.outer * self.outer;
+        if a > 1e-12 {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let root = discriminant.sqrt();
+            near = near.max((-b - root) / (2.0 * a));
+            far = far.min((-b + root) / (2.0 * a));
+        } else if c > 0.0 {
+            return None;
+        }
+
+        near = near.max(0.0);
+        (near < far).then_some((near, far))
+    }
+
+    // Pixels the slab can cover, or the whole screen if part of it is behind
+    // the camera
+    fn screen_bounds(
+        &self,
+        uniforms: &Uniforms,
+        width: usize,
+        height: usize,
+        thickness: f32,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let model_view_projection = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix;
+        let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+
+        for corner in 0..8 {
+            let pick = |bit: usize, extent: f32| if corner & bit == 0 { -extent } else { extent };
+            let local = Vec4::new(pick(1, self.outer), pick(2, thickness * 0.5), pick(4, self.outer), 1.0);
+            let clip = model_view_projection * local;
+            if clip.w <= 0.0 {
+                return Some((0, 0, width - 1, height - 1));
+            }
+            let ndc = clip.xyz() / clip.w;
+            let screen = uniforms.viewport_matrix * Vec4::new(ndc.x, ndc.y, ndc.z, 1.0);
+            min_x = min_x.min(screen.x);
+            min_y = min_y.min(screen.y);
+            max_x = max_x.max(screen.x);
+            max_y = max_y.max(screen.y);
+        }
+
+        if max_x < 0.0 || max_y < 0.0 || min_x >= width as f32 || min_y >= height as f32 {
+            return None;
+        }
+        Some((
+            min_x.max(0.0) as usize,
+            min_y.max(0.0) as usize,
+            (max_x.ceil() as usize).min(width - 1),
+            (max_y.ceil() as usize).min(height - 1),
+        ))
+    }
+}
+
+// Share of scattered light going off at an angle with cosine `cos_angle` from
+// the incoming direction, 1 for every angle when `asymmetry` is 0
+fn henyey_greenstein(asymmetry: f32, cos_angle: f32) -> f32 {
+    let g2 = asymmetry * asymmetry;
+    let denominator = 1.0 + g2 - 2.0 * asymmetry * cos_angle;
+    (1.0 - g2) / (denominator * denominator.sqrt())
+}
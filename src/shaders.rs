@@ -1,5 +1,7 @@
+use crate::bump::{bump_normal, normal_map_normal};
 use crate::color::Color;
 use crate::fragment::Fragment;
+use crate::material::Material;
 use crate::vertex::Vertex;
 use crate::Uniforms;
 use nalgebra_glm::{mat4_to_mat3, Mat3, Vec3, Vec4};
@@ -8,17 +10,20 @@ use rand::Rng;
 use rand::SeedableRng;
 
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+    // Displacement: the vertex moves along its normal but keeps its original
+    // `position`, so the fragment shaders sample their noise at the same points
+    let (displaced_position, normal) = match &uniforms.displacement {
+        Some(displacement) => displacement.apply(&vertex.position, &vertex.normal, uniforms),
+        None => (vertex.position, vertex.normal),
+    };
+
     // Transform position
-    let position = Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
+    let position = Vec4::new(displaced_position.x, displaced_position.y, displaced_position.z, 1.0);
     let transformed =
         uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * position;
 
-    // Perform perspective division
-    let w = transformed.w;
-    let ndc_position = Vec4::new(transformed.x / w, transformed.y / w, transformed.z / w, 1.0);
-
-    // apply viewport matrix
-    let screen_position = uniforms.viewport_matrix * ndc_position;
+    // Perform perspective division and apply viewport matrix
+    let screen_position = clip_to_screen(&transformed, uniforms);
 
     // Transform normal
     let model_mat3 = mat4_to_mat3(&uniforms.model_matrix);
@@ -27,19 +32,42 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
         .try_inverse()
         .unwrap_or(Mat3::identity());
 
-    let transformed_normal = normal_matrix * vertex.normal;
+    let transformed_normal = normal_matrix * normal;
+
+    // Tangents follow the surface, so they use the model matrix itself
+    let tangent = model_mat3 * vertex.tangent.xyz();
+    let transformed_tangent = Vec4::new(tangent.x, tangent.y, tangent.z, vertex.tangent.w);
 
     // Create a new Vertex with transformed attributes
     Vertex {
         position: vertex.position,
         normal: vertex.normal,
         tex_coords: vertex.tex_coords,
+        tangent: vertex.tangent,
         color: vertex.color,
-        transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
+        clip_position: transformed,
+        transformed_position: screen_position,
         transformed_normal,
+        transformed_tangent,
     }
 }
 
+// División de perspectiva y viewport: posición en pantalla con la profundidad
+// del buffer en z. También la usa el recorte para los vértices que crea.
+pub fn clip_to_screen(clip: &Vec4, uniforms: &Uniforms) -> Vec3 {
+    let w = clip.w;
+    let ndc_position = Vec4::new(
+        clip.x / w,
+        clip.y / w,
+        uniforms.depth.depth(clip.z / w, w),
+        1.0,
+    );
+
+    // apply viewport matrix
+    let screen_position = uniforms.viewport_matrix * ndc_position;
+    Vec3::new(screen_position.x, screen_position.y, screen_position.z)
+}
+
 pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     lava_shader(fragment, uniforms)
 }
@@ -194,41 +222,41 @@ fn lava_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     color * fragment.intensity
 }
 
-pub fn shader_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    // Posición y normal del fragmento
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
+// Iluminación difusa con término ambiental, compartida por los shaders de planetas
+fn diffuse_lighting(base_color: Color, fragment: &Fragment, ambient_intensity: f32) -> Color {
+    lighting_with_normal(base_color, fragment, &fragment.normal, ambient_intensity)
+}
 
-    // Iluminación
+// Igual que diffuse_lighting pero con una normal perturbada (bump o normal map)
+fn lighting_with_normal(
+    base_color: Color,
+    fragment: &Fragment,
+    normal: &Vec3,
+    ambient_intensity: f32,
+) -> Color {
+    let position = fragment.vertex_position;
+    let normal = normal.normalize();
     let light_pos = Vec3::new(0.0, 0.0, 20.0);
     let light_dir = (light_pos - position).normalize();
     let diffuse_intensity = normal.dot(&light_dir).max(0.0);
 
-    // Variable de tiempo para animación
-    let time = uniforms.time * 0.0001;
-
-    // Parámetros de umbral
-    let land_threshold = 0.5;
-    let cloud_threshold = 0.7;
+    let lit_color = base_color * diffuse_intensity;
+    let ambient_color = base_color * ambient_intensity;
+    let final_color = ambient_color + lit_color;
 
-    // Colores base
-    let water_color = Color::from_float(0.0, 0.1, 0.4); // Color del agua
-    let low_land_color = Color::from_float(0.2, 0.5, 0.2); // Tierras bajas
-    let high_land_color = Color::from_float(0.5, 0.4, 0.3); // Montañas
-    let snow_color = Color::from_float(1.0, 1.0, 1.0); // Nieve
-    let cloud_color = Color::from_float(0.8, 0.8, 0.8); // Nubes
-    let atmosphere_color = Color::from_float(0.0, 0.4, 0.8); // Azul de la atmósfera
+    final_color.clamp()
+}
 
-    // Velocidades de movimiento
+// Terreno de la Tierra normalizado a 0..1, el agua queda por debajo de 0.5
+fn earth_terrain(position: &Vec3, uniforms: &Uniforms) -> f32 {
+    // Variable de tiempo para animación
+    let time = uniforms.time * 0.0001;
     let land_speed = 0.01;
-    let cloud_speed = 0.03;
 
     // Obtener referencias a los ruidos
     let mountain_noise = uniforms.noises[0];
     let hill_noise = uniforms.noises[1];
     let detail_noise = uniforms.noises[2];
-    let cloud_noise = uniforms.noises[3];
-    let atmosphere_noise = uniforms.noises[4];
 
     // Ruido combinado para el terreno
     let mountain_value = mountain_noise.get_noise_3d(
@@ -253,25 +281,76 @@ pub fn shader_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let terrain_value =
         (mountain_value * 0.5 + hill_value * 0.3 + detail_value * 0.2).clamp(-1.0, 1.0);
 
-    let terrain_normalized = (terrain_value + 1.0) * 0.5;
+    (terrain_value + 1.0) * 0.5
+}
+
+const EARTH_LAND_THRESHOLD: f32 = 0.5;
+
+pub fn surface_earth(position: &Vec3, uniforms: &Uniforms) -> Color {
+    // Colores base
+    let water_color = Color::from_float(0.0, 0.1, 0.4); // Color del agua
+    let low_land_color = Color::from_float(0.2, 0.5, 0.2); // Tierras bajas
+    let high_land_color = Color::from_float(0.5, 0.4, 0.3); // Montañas
+    let snow_color = Color::from_float(1.0, 1.0, 1.0); // Nieve
+
+    let terrain_normalized = earth_terrain(position, uniforms);
 
     // Determinar si el fragmento es tierra o agua
-    let is_land = terrain_normalized > land_threshold;
+    let is_land = terrain_normalized > EARTH_LAND_THRESHOLD;
 
     // Color base según la altura del terreno
-    let mut base_color = if is_land {
+    if is_land {
         // Interpolar entre tierras bajas y altas
-        let land_height =
-            ((terrain_normalized - land_threshold) / (1.0 - land_threshold)).clamp(0.0, 1.0);
+        let land_height = ((terrain_normalized - EARTH_LAND_THRESHOLD)
+            / (1.0 - EARTH_LAND_THRESHOLD))
+            .clamp(0.0, 1.0);
 
         // Agregar nieve en las montañas altas
         let land_color = low_land_color.lerp(&high_land_color, land_height);
-        let with_snow = land_color.lerp(&snow_color, land_height.powf(3.0));
-
-        with_snow
+        land_color.lerp(&snow_color, land_height.powf(3.0))
     } else {
         water_color
-    };
+    }
+}
+
+// El océano es plano, solo la tierra tiene relieve
+pub fn height_earth(position: &Vec3, uniforms: &Uniforms) -> f32 {
+    earth_terrain(position, uniforms).max(EARTH_LAND_THRESHOLD)
+}
+
+// Capas animadas de la Tierra (nubes y atmósfera) sobre un color de superficie,
+// ya sea procedural o leído de un mapa horneado
+fn earth_sky_layers(
+    surface_color: Color,
+    normal: &Vec3,
+    fragment: &Fragment,
+    uniforms: &Uniforms,
+) -> Color {
+    // Posición y normal del fragmento
+    let position = fragment.vertex_position;
+    let normal = normal.normalize();
+
+    // Iluminación
+    let light_pos = Vec3::new(0.0, 0.0, 20.0);
+    let light_dir = (light_pos - position).normalize();
+    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+
+    // Variable de tiempo para animación
+    let time = uniforms.time * 0.0001;
+
+    // Parámetros de umbral
+    let cloud_threshold = 0.7;
+
+    // Colores base
+    let cloud_color = Color::from_float(0.8, 0.8, 0.8); // Nubes
+    let atmosphere_color = Color::from_float(0.0, 0.4, 0.8); // Azul de la atmósfera
+
+    // Velocidades de movimiento
+    let cloud_speed = 0.03;
+
+    // Obtener referencias a los ruidos
+    let cloud_noise = uniforms.noises[3];
+    let atmosphere_noise = uniforms.noises[4];
 
     // Ruido para las nubes
     let cloud_noise_value = cloud_noise.get_noise_3d(
@@ -286,7 +365,7 @@ pub fn shader_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         ((cloud_normalized - cloud_threshold) / (1.0 - cloud_threshold)).clamp(0.0, 1.0);
 
     // Mezclar las nubes con el color base
-    base_color = base_color.lerp(&cloud_color, cloud_opacity);
+    let base_color = surface_color.lerp(&cloud_color, cloud_opacity);
 
     // Aplicar iluminación al color base (antes de agregar la atmósfera)
     let lit_color = base_color * diffuse_intensity;
@@ -318,13 +397,27 @@ pub fn shader_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     final_color.clamp()
 }
 
-pub fn shader_jupiter(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let surface_color = surface_earth(&fragment.vertex_position, uniforms);
+    earth_sky_layers(surface_color, &fragment.normal, fragment, uniforms)
+}
+
+// La superficie viene de uniforms.textures[0] (un mapa real o uno horneado con
+// surface_earth) y solo las nubes y la atmósfera se calculan en cada frame.
+// uniforms.textures[1], si existe, es el mapa de normales del relieve.
+pub fn shader_earth_textured(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let surface_color = match uniforms.textures.first() {
+        Some(texture) => texture.sample_color(fragment.tex_coords, fragment.uv_footprint),
+        None => surface_earth(&fragment.vertex_position, uniforms),
+    };
+    let normal = match uniforms.textures.get(1) {
+        Some(normal_map) => normal_map_normal(fragment, normal_map),
+        None => fragment.normal,
+    };
+    earth_sky_layers(surface_color, &normal, fragment, uniforms)
+}
 
+pub fn surface_jupiter(position: &Vec3, uniforms: &Uniforms) -> Color {
     let band_noise_value = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
     let high_clouds_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
     let deep_atmospheric_noise =
@@ -341,26 +434,16 @@ pub fn shader_jupiter(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 
     let base_color = color1.lerp(&color2, normalized_band_value);
     let clouds_color = base_color.lerp(&high_clouds_color, normalized_high_clouds);
-    let mut final_color = clouds_color.lerp(&deep_color, normalized_deep_atmos);
-
-    let lit_color = final_color * diffuse_intensity;
-    let ambient_intensity = 0.1;
-    let ambient_color = final_color * ambient_intensity;
-    final_color = ambient_color + lit_color;
-
-    final_color.clamp()
+    clouds_color.lerp(&deep_color, normalized_deep_atmos)
 }
 
-pub fn shader_moon(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    // Posición y normal del fragmento
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-
-    // Iluminación
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_jupiter(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_jupiter(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.1)
+}
 
+// Manchas de la luna normalizadas a 0..1, se usan como color y como altura
+fn moon_terrain(position: &Vec3, uniforms: &Uniforms) -> f32 {
     // Obtener referencias a los ruidos
     let noise1 = uniforms.noises[0];
     let noise2 = uniforms.noises[1];
@@ -392,27 +475,28 @@ pub fn shader_moon(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let combined_noise =
         (noise_value1 * 0.6 + noise_value2 * 0.3 + noise_value3 * 0.1).clamp(-1.0, 1.0);
 
-    let normalized_value = (combined_noise + 1.0) * 0.5;
+    (combined_noise + 1.0) * 0.5
+}
+
+pub fn surface_moon(position: &Vec3, uniforms: &Uniforms) -> Color {
+    let normalized_value = moon_terrain(position, uniforms);
 
     // Definir colores para las partes claras y oscuras de la luna
     let light_gray = Color::from_float(0.9, 0.9, 0.9);
     let dark_gray = Color::from_float(0.001, 0.001, 0.001);
 
     // Interpolar entre los colores basado en el valor de ruido
-    let base_color = dark_gray.lerp(&light_gray, normalized_value);
-
-    // Aplicar iluminación difusa
-    let lit_color = base_color * diffuse_intensity;
-
-    // Añadir un término ambiental
-    let ambient_intensity = 0.2;
-    let ambient_color = base_color * ambient_intensity;
+    dark_gray.lerp(&light_gray, normalized_value)
+}
 
-    // Combinar los componentes ambiental y difuso
-    let final_color = ambient_color + lit_color;
+pub fn height_moon(position: &Vec3, uniforms: &Uniforms) -> f32 {
+    moon_terrain(position, uniforms)
+}
 
-    // Asegurar que los valores de color estén en el rango válido
-    final_color.clamp()
+pub fn shader_moon(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_moon(&fragment.vertex_position, uniforms);
+    let normal = bump_normal(fragment, uniforms, height_moon, 0.02);
+    lighting_with_normal(base_color, fragment, &normal, 0.2)
 }
 
 pub fn shader_ring(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -456,13 +540,7 @@ pub fn shader_ring(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     final_color.clamp()
 }
 
-pub fn shader_venus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
-
+pub fn surface_venus(position: &Vec3, uniforms: &Uniforms) -> Color {
     let surface_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
     let atmosphere_noise =
         uniforms.noises[1].get_noise_3d(position.x * 0.1, position.y * 0.1, position.z * 0.1);
@@ -471,24 +549,16 @@ pub fn shader_venus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let cloud_color = Color::from_float(0.9, 0.85, 0.7); // Sulphuric clouds
     let glow_color = Color::from_float(0.95, 0.65, 0.2); // Warm atmospheric glow
 
-    let mut base_color = surface_color.lerp(&cloud_color, surface_noise.abs());
-    base_color = base_color.lerp(&glow_color, atmosphere_noise.abs());
-
-    let lit_color = base_color * diffuse_intensity;
-    let ambient_intensity = 0.2;
-    let ambient_color = base_color * ambient_intensity;
-    let final_color = ambient_color + lit_color;
-
-    final_color.clamp()
+    let base_color = surface_color.lerp(&cloud_color, surface_noise.abs());
+    base_color.lerp(&glow_color, atmosphere_noise.abs())
 }
 
-pub fn shader_mercury(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_venus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_venus(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.2)
+}
 
+pub fn surface_mercury(position: &Vec3, uniforms: &Uniforms) -> Color {
     let crater_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
     let texture_noise =
         uniforms.noises[1].get_noise_3d(position.x * 10.0, position.y * 10.0, position.z * 10.0);
@@ -501,24 +571,22 @@ pub fn shader_mercury(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 
     let crater_base = base_color.lerp(&dark_crater_color, crater_noise.abs());
     let textured_color = crater_base.lerp(&highlight_color, texture_noise.abs());
-    let mut final_color = textured_color.lerp(&base_color, undulation_noise.abs());
-
-    let lit_color = final_color * diffuse_intensity;
-    let ambient_intensity = 0.2;
-    let ambient_color = final_color * ambient_intensity;
-    final_color = ambient_color + lit_color;
+    textured_color.lerp(&base_color, undulation_noise.abs())
+}
 
-    final_color.clamp()
+// Los cráteres son los valles del ruido celular
+pub fn height_mercury(position: &Vec3, uniforms: &Uniforms) -> f32 {
+    let crater_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+    1.0 - crater_noise.abs().min(1.0)
 }
 
-pub fn shader_mars(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_mercury(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_mercury(&fragment.vertex_position, uniforms);
+    let normal = bump_normal(fragment, uniforms, height_mercury, 0.05);
+    lighting_with_normal(base_color, fragment, &normal, 0.2)
+}
 
-    let surface_value = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+pub fn surface_mars(position: &Vec3, uniforms: &Uniforms) -> Color {
     let detail_value = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
     let atmospheric_value = uniforms.noises[2].get_noise_3d(position.x, position.y, position.z);
 
@@ -526,21 +594,22 @@ pub fn shader_mars(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let detail_color = Color::from_float(0.12, 0.09, 0.05); // Detalles más claros
     let atmospheric_color = Color::from_float(0.9, 0.4, 0.3); // Tono atmosférico
 
-    let combined_color = base_color
+    base_color
         .lerp(&detail_color, detail_value.abs())
-        .lerp(&atmospheric_color, atmospheric_value.abs());
-    let final_color = combined_color * diffuse_intensity;
+        .lerp(&atmospheric_color, atmospheric_value.abs())
+}
 
-    final_color.clamp()
+pub fn height_mars(position: &Vec3, uniforms: &Uniforms) -> f32 {
+    let surface_value = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+    (surface_value + 1.0) * 0.5
 }
 
-pub fn shader_phobos(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_mars(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_mars(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
+}
 
+pub fn surface_phobos(position: &Vec3, uniforms: &Uniforms) -> Color {
     let crater_noise = uniforms.noises[2].get_noise_3d(position.x, position.y, position.z);
     let surface_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
     let detail_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
@@ -549,22 +618,35 @@ pub fn shader_phobos(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let dark_crater_color = Color::from_float(0.3, 0.3, 0.3); // Shadow in craters
     let highlight_color = Color::from_float(0.7, 0.7, 0.6); // Sunlit edges
 
-    let final_color = base_color
+    base_color
         .lerp(&base_color, crater_noise.abs())
         .lerp(&dark_crater_color, surface_noise.abs())
-        .lerp(&highlight_color, detail_noise.abs());
-    let lit_color = final_color * diffuse_intensity;
+        .lerp(&highlight_color, detail_noise.abs())
+}
 
-    lit_color.clamp()
+pub fn height_phobos(position: &Vec3, uniforms: &Uniforms) -> f32 {
+    let crater_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+    1.0 - crater_noise.abs().min(1.0)
 }
 
-pub fn shader_saturn(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn shader_phobos(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_phobos(&fragment.vertex_position, uniforms);
+    let normal = bump_normal(fragment, uniforms, height_phobos, 0.05);
+    lighting_with_normal(base_color, fragment, &normal, 0.0)
+}
+
+// Roca gris con manchas; el color de cada asteroide llega como tinte de su instancia
+pub fn shader_asteroid(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+    let mottling = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+
+    let light_rock = Color::from_float(0.75, 0.73, 0.7);
+    let dark_rock = Color::from_float(0.4, 0.38, 0.36);
+    let base_color = light_rock.lerp(&dark_rock, mottling.abs());
+    diffuse_lighting(base_color, fragment, 0.08)
+}
 
+pub fn surface_saturn(position: &Vec3, uniforms: &Uniforms) -> Color {
     let band_value = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
     let cloud_value = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
 
@@ -572,31 +654,28 @@ pub fn shader_saturn(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let band_color = Color::from_float(0.7, 0.7, 0.5); // Slightly yellow for bands
     let cloud_color = Color::from_float(0.9, 0.9, 0.7); // Lighter color for clouds
 
-    let color = base_color
+    base_color
         .lerp(&band_color, (band_value + 1.0) * 0.5)
-        .lerp(&cloud_color, cloud_value.abs());
-
-    let lit_color = color * diffuse_intensity;
-
-    lit_color.clamp()
+        .lerp(&cloud_color, cloud_value.abs())
 }
 
-pub fn shader_uranus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_dir = (Vec3::new(0.0, 0.0, 20.0) - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_saturn(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_saturn(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
+}
 
-    let primary_value = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+pub fn surface_uranus(position: &Vec3, uniforms: &Uniforms) -> Color {
     let secondary_value = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
 
     let base_color = Color::from_float(0.4, 0.5, 0.6); // Color base para Urano
     let secondary_color = Color::from_float(0.3, 0.4, 0.5); // Color secundario para dar más profundidad
 
-    let combined_color = base_color.lerp(&secondary_color, secondary_value.abs());
-    let final_color = combined_color * diffuse_intensity;
+    base_color.lerp(&secondary_color, secondary_value.abs())
+}
 
-    final_color.clamp()
+pub fn shader_uranus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_uranus(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
 }
 
 pub fn shader_uranus_ring(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -617,78 +696,116 @@ pub fn shader_uranus_ring(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     final_color.clamp()
 }
 
-pub fn shader_neptune(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
-
-    let surface_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+pub fn surface_neptune(position: &Vec3, uniforms: &Uniforms) -> Color {
     let atmosphere_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
 
     let base_color = Color::from_float(0.2, 0.2, 0.6);
     let atmosphere_color = Color::from_float(0.1, 0.1, 0.7);
 
-    let combined_color = base_color.lerp(&atmosphere_color, atmosphere_noise.abs());
-    let final_color = combined_color * diffuse_intensity;
-
-    final_color.clamp()
+    base_color.lerp(&atmosphere_color, atmosphere_noise.abs())
 }
 
-pub fn shader_pluto(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_neptune(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_neptune(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
+}
 
-    let surface_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+pub fn surface_pluto(position: &Vec3, uniforms: &Uniforms) -> Color {
     let ice_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
 
     let base_color = Color::from_float(0.5, 0.5, 0.5);
     let ice_color = Color::from_float(0.8, 0.8, 0.9);
 
-    let combined_color = base_color.lerp(&ice_color, ice_noise.abs());
-    let final_color = combined_color * diffuse_intensity;
-
-    final_color.clamp()
+    base_color.lerp(&ice_color, ice_noise.abs())
 }
 
-pub fn shader_eris(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
-    let normal = fragment.normal.normalize();
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let light_dir = (light_pos - position).normalize();
-    let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+pub fn shader_pluto(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_pluto(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
+}
 
-    let surface_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
+pub fn surface_eris(position: &Vec3, uniforms: &Uniforms) -> Color {
     let ice_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
 
     let base_color = Color::from_float(0.6, 0.5, 0.4);
     let ice_color = Color::from_float(0.7, 0.7, 0.8);
 
-    let combined_color = base_color.lerp(&ice_color, ice_noise.abs());
-    let final_color = combined_color * diffuse_intensity;
+    base_color.lerp(&ice_color, ice_noise.abs())
+}
 
-    final_color.clamp()
+pub fn shader_eris(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = surface_eris(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
+}
+
+pub fn surface_sedna(position: &Vec3, uniforms: &Uniforms) -> Color {
+    let ice_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
+
+    let base_color = Color::from_float(0.4, 0.3, 0.3);
+    let ice_color = Color::from_float(0.5, 0.5, 0.6);
+
+    base_color.lerp(&ice_color, ice_noise.abs())
 }
 
 pub fn shader_sedna(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let position = fragment.vertex_position;
+    let base_color = surface_sedna(&fragment.vertex_position, uniforms);
+    diffuse_lighting(base_color, fragment, 0.0)
+}
+
+pub fn shader_material(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let default_material = Material::default();
+    let material = uniforms.material.unwrap_or(&default_material);
+
+    // Blinn-Phong en espacio de mundo
+    let local = fragment.vertex_position;
+    let world = uniforms.model_matrix * Vec4::new(local.x, local.y, local.z, 1.0);
+    let position = Vec3::new(world.x, world.y, world.z);
     let normal = fragment.normal.normalize();
+
     let light_pos = Vec3::new(0.0, 0.0, 20.0);
     let light_dir = (light_pos - position).normalize();
+
+    // La posición de la cámara se recupera de la matriz de vista: eye = -R^T * t
+    let view_rotation = mat4_to_mat3(&uniforms.view_matrix);
+    let view_translation = Vec3::new(
+        uniforms.view_matrix[(0, 3)],
+        uniforms.view_matrix[(1, 3)],
+        uniforms.view_matrix[(2, 3)],
+    );
+    let eye = -(view_rotation.transpose() * view_translation);
+    let view_dir = (eye - position).normalize();
+    let half_dir = (light_dir + view_dir).normalize();
+
     let diffuse_intensity = normal.dot(&light_dir).max(0.0);
+    let specular_intensity = if diffuse_intensity > 0.0 {
+        normal.dot(&half_dir).max(0.0).powf(material.shininess.max(1.0))
+    } else {
+        0.0
+    };
 
-    let surface_noise = uniforms.noises[0].get_noise_3d(position.x, position.y, position.z);
-    let ice_noise = uniforms.noises[1].get_noise_3d(position.x, position.y, position.z);
+    let ambient_intensity = 0.1;
+    let color = material.ambient.component_mul(&material.diffuse) * ambient_intensity
+        + material.diffuse * diffuse_intensity
+        + material.specular * specular_intensity
+        + material.emissive;
 
-    let base_color = Color::from_float(0.4, 0.3, 0.3);
-    let ice_color = Color::from_float(0.5, 0.5, 0.6);
+    // Sin mezcla alfa en el framebuffer, la opacidad atenúa hacia el negro
+    let color = color * material.opacity;
+
+    Color::from_float(color.x, color.y, color.z)
+}
 
-    let combined_color = base_color.lerp(&ice_color, ice_noise.abs());
-    let final_color = combined_color * diffuse_intensity;
+// uniforms.textures[0] es el color y uniforms.textures[1], opcional, el mapa de normales
+pub fn shader_textured(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    // Sin textura se pinta magenta para que el error sea evidente
+    let base_color = match uniforms.textures.first() {
+        Some(texture) => texture.sample_color(fragment.tex_coords, fragment.uv_footprint),
+        None => Color::new(255, 0, 255),
+    };
+    let normal = match uniforms.textures.get(1) {
+        Some(normal_map) => normal_map_normal(fragment, normal_map),
+        None => fragment.normal,
+    };
 
-    final_color.clamp()
+    lighting_with_normal(base_color, fragment, &normal, 0.2)
 }
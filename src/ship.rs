@@ -0,0 +1,118 @@
+use nalgebra_glm::{
+    quat_angle_axis, quat_normalize, quat_rotate_vec3, quat_to_mat4, scaling, translation, Mat4,
+    Quat, Vec3,
+};
+
+// SpaceShip.obj has its nose on +Z and its cockpit on +Y
+const MODEL_FORWARD: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+const MODEL_UP: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+const MODEL_RIGHT: Vec3 = Vec3::new(-1.0, 0.0, 0.0);
+
+// Ship-relative inputs for one frame, each in -1.0..=1.0
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShipControls {
+    pub thrust: f32,
+    pub strafe_right: f32,
+    pub strafe_up: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub roll: f32,
+    // Fires the thrusters against the current velocity
+    pub brake: bool,
+}
+
+pub struct Ship {
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub velocity: Vec3,
+    // Radians per second around the ship's right, up and forward axes
+    pub angular_velocity: Vec3,
+    pub scale: f32,
+    // Units per second squared at full thrust
+    pub acceleration: f32,
+    pub strafe_acceleration: f32,
+    // Radians per second squared at full stick
+    pub angular_acceleration: f32,
+    // Fraction of the spin the stabilizers remove per second
+    pub angular_damping: f32,
+}
+
+impl Ship {
+    pub fn new(position: Vec3, forward: Vec3) -> Self {
+        // Turn the model's nose towards `forward` around the vertical axis
+        let yaw = forward.x.atan2(forward.z);
+        Ship {
+            position,
+            orientation: quat_angle_axis(yaw, &Vec3::y()),
+            velocity: Vec3::zeros(),
+            angular_velocity: Vec3::zeros(),
+            scale: 0.05,
+            acceleration: 2.0,
+            strafe_acceleration: 1.0,
+            angular_acceleration: 3.0,
+            angular_damping: 4.0,
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        quat_rotate_vec3(&self.orientation, &MODEL_FORWARD)
+    }
+
+    pub fn up(&self) -> Vec3 {
+        quat_rotate_vec3(&self.orientation, &MODEL_UP)
+    }
+
+    pub fn right(&self) -> Vec3 {
+        quat_rotate_vec3(&self.orientation, &MODEL_RIGHT)
+    }
+
+    // Thrusters change the velocity, nothing slows the ship down on its own.
+    // Rotation keeps a little stabilizer damping so the ship stays flyable.
+    pub fn update(&mut self, controls: &ShipControls, delta_time: f32) {
+        let mut acceleration = self.forward() * controls.thrust * self.acceleration
+            + self.right() * controls.strafe_right * self.strafe_acceleration
+            + self.up() * controls.strafe_up * self.strafe_acceleration;
+
+        if controls.brake && self.velocity.magnitude() > 1e-4 {
+            let speed = self.velocity.magnitude();
+            // Never overshoot into reverse
+            let braking = self.acceleration.min(speed / delta_time.max(1e-4));
+            acceleration -= self.velocity / speed * braking;
+        }
+
+        self.velocity += acceleration * delta_time;
+        self.position += self.velocity * delta_time;
+
+        let stick = Vec3::new(controls.pitch, controls.yaw, controls.roll);
+        self.angular_velocity += stick * self.angular_acceleration * delta_time;
+        self.angular_velocity *= (1.0 - self.angular_damping * delta_time).max(0.0);
+
+        // Rotations are around the ship's own axes, so they compose on the right
+        let local = [
+            (self.angular_velocity.x, MODEL_RIGHT),
+            (self.angular_velocity.y, MODEL_UP),
+            (self.angular_velocity.z, MODEL_FORWARD),
+        ];
+        for (rate, axis) in local {
+            if rate != 0.0 {
+                self.orientation *= quat_angle_axis(rate * delta_time, &axis);
+            }
+        }
+        self.orientation = quat_normalize(&self.orientation);
+    }
+
+    pub fn model_matrix(&self) -> Mat4 {
+        translation(&self.position)
+            * quat_to_mat4(&self.orientation)
+            * scaling(&Vec3::new(self.scale, self.scale, self.scale))
+    }
+
+    // Eye, center and up of a third-person camera behind and above the ship
+    pub fn chase_view(&self) -> (Vec3, Vec3, Vec3) {
+        let forward = self.forward();
+        let up = self.up();
+        let eye = self.position - forward * 1.2 + up * 0.35;
+        let center = self.position + forward * 2.0;
+        (eye, center, up)
+    }
+}
@@ -1,65 +1,238 @@
+use crate::catalog::CatalogStar;
+use crate::color::Color;
+use crate::line::{line, LineStyle};
+use crate::picking::ScreenRays;
+use crate::projection::{projection_matrix, DepthRange, Projection};
+use crate::star::{
+    blackbody_color, color_index_temperature, fainter_share, magnitude_intensity, random_magnitude,
+    SpectralClass,
+};
+use crate::texture::{equirectangular_uv, CubeMap, Texture};
 use crate::{Framebuffer, Uniforms};
-use nalgebra_glm::{Vec3, Vec4};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
 use rand::prelude::*;
 use std::f32::consts::PI;
 
+// Width in pixels of the blur spreading a star over its neighbours
+const STAR_SIGMA: f32 = 0.55;
+// Distance from the eye the stars are drawn at, well inside the depth range
+const SKY_RADIUS: f32 = 100.0;
+// Field of view the sky is seen with while the scene is orthographic
+const ORTHOGRAPHIC_SKY_FOV: f32 = PI / 3.0;
+// How far scintillation swings a star's intensity either way
+const TWINKLE_AMOUNT: f32 = 0.3;
+// Twinkle speeds in radians per unit of `Uniforms::time`
+const TWINKLE_SPEED: (f32, f32) = (0.002, 0.006);
+const CONSTELLATION_COLOR: Color = Color::new(70, 110, 170);
+const CONSTELLATION_ALPHA: f32 = 0.6;
+
 pub struct Star {
-    position: Vec3,
-    brightness: f32,
-    size: u8,
+    // Unit vector; stars are infinitely far away, so only where they are
+    // matters, never how far
+    direction: Vec3,
+    color: Color,
+    // Peak intensity, above 1.0 for stars that spill into a wider disc
+    intensity: f32,
+    // Per-star scintillation: a speed and two phases, fixed at creation
+    twinkle_speed: f32,
+    twinkle_phase: (f32, f32),
+}
+
+impl Star {
+    // Fixed attributes aside, the twinkle comes from a hash of the star's
+    // index, so catalog and generated skies twinkle the same on every run
+    fn new(index: usize, direction: Vec3, color: Color, intensity: f32) -> Self {
+        let random = |salt: u32| {
+            let mut hash = (index as u32).wrapping_mul(0x9E3779B9) ^ salt.wrapping_mul(0x85EBCA6B);
+            hash ^= hash >> 16;
+            hash = hash.wrapping_mul(0x7FEB352D);
+            hash ^= hash >> 15;
+            hash as f32 / u32::MAX as f32
+        };
+
+        Star {
+            direction,
+            color,
+            intensity,
+            twinkle_speed: TWINKLE_SPEED.0 + (TWINKLE_SPEED.1 - TWINKLE_SPEED.0) * random(1),
+            twinkle_phase: (random(2) * 2.0 * PI, random(3) * 2.0 * PI),
+        }
+    }
+
+    // Intensity at `time`: two incommensurate sines so the pattern never
+    // visibly repeats
+    fn intensity_at(&self, time: f32) -> f32 {
+        let angle = time * self.twinkle_speed;
+        let wave = 0.6 * (angle + self.twinkle_phase.0).sin()
+            + 0.4 * (angle * 2.37 + self.twinkle_phase.1).sin();
+        self.intensity * (1.0 + TWINKLE_AMOUNT * wave)
+    }
+}
+
+// View and projection for everything at infinity. The view keeps only the
+// camera's rotation, so the sky looks the same wherever the camera goes, and
+// orthographic views borrow a perspective so directions still spread over
+// the screen instead of collapsing onto one point. `depth` must be the range
+// `projection` was built with, so constellation lines keep its depth order.
+pub fn sky_matrices(view: &Mat4, projection: &Mat4, depth: &DepthRange) -> (Mat4, Mat4) {
+    let mut rotation = *view;
+    rotation[(0, 3)] = 0.0;
+    rotation[(1, 3)] = 0.0;
+    rotation[(2, 3)] = 0.0;
+
+    let projection = if projection[(3, 3)] != 0.0 {
+        let aspect_ratio = projection[(1, 1)] / projection[(0, 0)];
+        projection_matrix(Projection::Perspective, depth, aspect_ratio, ORTHOGRAPHIC_SKY_FOV, 0.0)
+    } else {
+        *projection
+    };
+
+    (rotation, projection)
+}
+
+// Artist-made image of the sky, behind the stars
+pub enum Backdrop {
+    CubeMap(CubeMap),
+    Equirectangular(Texture),
+}
+
+impl Backdrop {
+    // Six faces `<dir>/<name>_<px|nx|py|ny|pz|nz>.png` or, failing that, a
+    // panorama `<dir>/<name>.png` or `.jpg`
+    pub fn load(dir: &str, name: &str) -> Result<Backdrop, image::ImageError> {
+        if let Ok(cube_map) = CubeMap::load(dir, name) {
+            return Ok(Backdrop::CubeMap(cube_map));
+        }
+        Texture::load(&format!("{}/{}.png", dir, name))
+            .or_else(|_| Texture::load(&format!("{}/{}.jpg", dir, name)))
+            .map(Backdrop::Equirectangular)
+    }
+
+    pub fn sample(&self, direction: &Vec3) -> Vec3 {
+        match self {
+            Backdrop::CubeMap(cube_map) => cube_map.sample(direction),
+            // Longitude repeats across the panorama's left and right edges
+            Backdrop::Equirectangular(texture) => texture.sample(equirectangular_uv(direction)),
+        }
+    }
 }
 
 pub struct Skybox {
     stars: Vec<Star>,
+    // Constellation segments, as pairs of indices into `stars`
+    lines: Vec<(usize, usize)>,
+    backdrop: Option<Backdrop>,
 }
 
 impl Skybox {
-    pub fn new(star_count: usize) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut stars = Vec::with_capacity(star_count);
+    // The same seed always gives the same sky
+    pub fn new(star_count: usize, seed: u64) -> Self {
+        Skybox {
+            stars: Vec::new(),
+            lines: Vec::new(),
+            backdrop: None,
+        }
+        .with_generated_stars(star_count, seed, f32::NEG_INFINITY)
+    }
 
-        for _ in 0..star_count {
-            // Generate random spherical coordinates
+    // The real sky, with `lines` indexing into `catalog`
+    pub fn from_catalog(catalog: &[CatalogStar], lines: Vec<(usize, usize)>) -> Self {
+        let stars = catalog
+            .iter()
+            .enumerate()
+            .map(|(index, star)| {
+                Star::new(
+                    index,
+                    star.direction,
+                    blackbody_color(color_index_temperature(star.color_index)),
+                    magnitude_intensity(star.magnitude),
+                )
+            })
+            .collect();
+
+        Skybox {
+            stars,
+            lines,
+            backdrop: None,
+        }
+    }
+
+    // Adds the generated stars fainter than `brightest` out of a sky of
+    // `star_count`, for a catalog that is only complete down to `brightest`:
+    // its stars keep their places and the generated ones fill in the rest
+    pub fn with_generated_stars(mut self, star_count: usize, seed: u64, brightest: f32) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let count = (star_count as f32 * fainter_share(brightest)).round() as usize;
+        self.stars.reserve(count);
+
+        for _ in 0..count {
+            // Uniform over the sphere: uniform azimuth and uniform height
             let theta = rng.gen::<f32>() * 2.0 * PI; // Azimuth angle
-            let phi = rng.gen::<f32>() * PI; // Polar angle
-            let radius = 100.0; // Fixed radius for all stars
-
-            // Convert spherical to Cartesian coordinates
-            let x = radius * phi.sin() * theta.cos();
-            let y = radius * phi.cos();
-            let z = radius * phi.sin() * theta.sin();
-
-            // Random brightness between 0.0 and 1.0
-            let brightness = rng.gen::<f32>();
-            let size: u8 = rng.gen_range(1..=3);
-
-            stars.push(Star {
-                position: Vec3::new(x, y, z),
-                brightness,
-                size,
-            });
+            let y = rng.gen_range(-1.0f32..1.0);
+            let ring = (1.0 - y * y).sqrt();
+
+            let class = SpectralClass::random(&mut rng);
+            let temperature = class.random_temperature(&mut rng);
+            let magnitude = random_magnitude(&mut rng, brightest);
+
+            self.stars.push(Star::new(
+                self.stars.len(),
+                Vec3::new(ring * theta.cos(), y, ring * theta.sin()),
+                blackbody_color(temperature),
+                magnitude_intensity(magnitude),
+            ));
         }
 
-        Skybox { stars }
+        self
     }
 
-    pub fn render(
+    pub fn with_backdrop(mut self, backdrop: Backdrop) -> Self {
+        self.backdrop = Some(backdrop);
+        self
+    }
+
+    pub fn has_backdrop(&self) -> bool {
+        self.backdrop.is_some()
+    }
+
+    pub fn has_constellations(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    // Fills every pixel with the backdrop along its view ray, at the far plane
+    // so everything else is drawn in front of it
+    pub fn render_backdrop(
         &self,
         framebuffer: &mut Framebuffer,
-        uniforms: &Uniforms,
-        camera_position: Vec3,
+        view: &Mat4,
+        projection: &Mat4,
+        depth: &DepthRange,
     ) {
-        // let mut rng = rand::thread_rng();
+        let Some(backdrop) = &self.backdrop else {
+            return;
+        };
+        let Some(rays) = ScreenRays::new(framebuffer.width, framebuffer.height, view, projection) else {
+            return;
+        };
 
-        for star in &self.stars {
-            // Calculate star position relative to camera
-            let position = star.position + camera_position;
+        for y in 0..framebuffer.height {
+            for x in 0..framebuffer.width {
+                let color = backdrop.sample(&rays.direction(x, y));
+                framebuffer.set_current_color(Color::from_float(color.x, color.y, color.z).to_hex());
+                framebuffer.point(x, y, depth.farthest());
+            }
+        }
+    }
 
-            // Project the star position to screen space
+    // `uniforms` should carry the matrices from `sky_matrices`
+    pub fn render(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        for star in &self.stars {
+            // Project the star's direction to screen space
+            let position = star.direction * SKY_RADIUS;
             let pos_vec4 = Vec4::new(position.x, position.y, position.z, 1.0);
             let projected = uniforms.projection_matrix * uniforms.view_matrix * pos_vec4;
 
-            // Perform perspective division
+            // Behind the camera
             if projected.w <= 0.0 {
                 continue;
             }
@@ -68,48 +241,49 @@ impl Skybox {
             // Apply viewport transform
             let screen_pos = uniforms.viewport_matrix * Vec4::new(ndc.x, ndc.y, ndc.z, 1.0);
 
-            // Check if star is in front of camera and within screen bounds
-            if screen_pos.z < 0.0 {
-                continue;
-            }
+            draw_star(framebuffer, screen_pos.x, screen_pos.y, star, star.intensity_at(uniforms.time));
+        }
+    }
+
+    // Faint lines between the stars of each constellation. Drawn right after
+    // the stars, so every body in front covers them. `uniforms` should carry
+    // the matrices from `sky_matrices`.
+    pub fn render_constellations(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        for &(a, b) in &self.lines {
+            let a = self.stars[a].direction * SKY_RADIUS;
+            let b = self.stars[b].direction * SKY_RADIUS;
+            line(
+                framebuffer,
+                uniforms,
+                &a,
+                &b,
+                &CONSTELLATION_COLOR,
+                CONSTELLATION_ALPHA,
+                LineStyle::Antialiased,
+            );
+        }
+    }
+}
+
+// Splats a star at a sub-pixel position as a small gaussian, added onto what
+// is there. Stars brighter than 1.0 keep a saturated core and grow wider
+// instead. The depth buffer is not touched: the sky is drawn first and
+// everything else covers it.
+fn draw_star(framebuffer: &mut Framebuffer, x: f32, y: f32, star: &Star, intensity: f32) {
+    let peak = intensity.min(1.0);
+    let sigma = STAR_SIGMA * intensity.max(1.0).sqrt();
+    let reach = (3.0 * sigma).ceil() as i32;
+
+    // Pixel centers sit at +0.5
+    let (center_x, center_y) = (x - 0.5, y - 0.5);
+    let (pixel_x, pixel_y) = (center_x.round() as i32, center_y.round() as i32);
 
-            let x = screen_pos.x as usize;
-            let y = screen_pos.y as usize;
-
-            if x < framebuffer.width && y < framebuffer.height {
-                // Random chance for star to blink
-                /*
-                let blink_chance = rng.gen::<f32>();
-                let blink_amount = if blink_chance < 0.3 {
-                    (rng.gen::<f32>() - 0.5) * 0.2 // This gives us -0.1 to 0.1 variation
-                } else {
-                    0.0
-                };
-                let adjusted_brightness = (star.brightness + blink_amount).clamp(0.0, 1.0);
-                */
-                let intensity = (star.brightness * 255.0) as u8;
-                let color = (intensity as u32) << 16 | (intensity as u32) << 8 | intensity as u32;
-
-                framebuffer.set_current_color(color);
-                // framebuffer.point(x, y, 1000.0);  // depth is high so things render in front
-
-                match star.size {
-                    1 => framebuffer.point(x, y, 1000.0),
-                    2 => {
-                        framebuffer.point(x, y, 1000.0);
-                        framebuffer.point(x + 1, y, 1000.0);
-                        framebuffer.point(x, y + 1, 1000.0);
-                        framebuffer.point(x + 1, y + 1, 1000.0);
-                    }
-                    3 => {
-                        framebuffer.point(x, y, 1000.0);
-                        framebuffer.point(x - 1, y, 1000.0);
-                        framebuffer.point(x + 1, y, 1000.0);
-                        framebuffer.point(x, y - 1, 1000.0);
-                        framebuffer.point(x, y + 1, 1000.0);
-                    }
-                    _ => {}
-                }
+    for py in (pixel_y - reach)..=(pixel_y + reach) {
+        for px in (pixel_x - reach)..=(pixel_x + reach) {
+            let (dx, dy) = (px as f32 - center_x, py as f32 - center_y);
+            let weight = peak * (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp();
+            if weight > 1.0 / 255.0 {
+                framebuffer.add_pixel(px, py, &(star.color * weight));
             }
         }
     }
@@ -0,0 +1,115 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::particle::{time_steps, ParticleSettings, ParticleSystem};
+use crate::sphere::random_direction;
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+use rand::prelude::*;
+
+// Same stepping as the comet's tails, in units of `Uniforms::time`
+const MAX_STEP: f32 = 200.0;
+const WARM_UP: f32 = 8000.0;
+
+// Wind particles per unit of time, and the speed they leave the surface at
+const WIND_RATE: f32 = 0.4;
+const WIND_SPEED: f32 = 6e-4;
+// Mean time between flares, particles thrown up by each, and how fast
+const FLARE_INTERVAL: f32 = 6000.0;
+const FLARE_PARTICLES: usize = 160;
+const FLARE_SPEED: f32 = 1.1e-3;
+// How long before the first frame the first flare goes off, so one is
+// already arcing when the scene opens
+const FIRST_FLARE: f32 = 1200.0;
+// Pull back towards the surface at the surface, falling off with the square
+// of the distance, so flares arc up and rain back down
+const SURFACE_GRAVITY: f32 = 5e-7;
+
+const WIND: ParticleSettings = ParticleSettings {
+    lifetime: (4000.0, 7000.0),
+    size: (0.04, 0.14),
+    color: (Color::new(255, 230, 170), Color::new(255, 170, 90)),
+    brightness: 0.07,
+    max_particles: 3000,
+};
+
+const FLARES: ParticleSettings = ParticleSettings {
+    lifetime: (2500.0, 4500.0),
+    size: (0.14, 0.06),
+    color: (Color::new(255, 245, 200), Color::new(230, 70, 20)),
+    brightness: 0.3,
+    max_particles: 800,
+};
+
+// Particles around the Sun: a faint solar wind streaming out of the whole
+// surface and, now and then, a flare of hot plasma from a single spot
+pub struct SolarActivity {
+    center: Vec3,
+    radius: f32,
+    wind: ParticleSystem,
+    flares: ParticleSystem,
+    rng: StdRng,
+    next_flare: Option<f32>,
+    simulated_until: Option<f32>,
+}
+
+impl SolarActivity {
+    // The same seed always gives the same flares
+    pub fn new(center: Vec3, radius: f32, seed: u64) -> Self {
+        SolarActivity {
+            center,
+            radius,
+            wind: ParticleSystem::new(WIND, seed),
+            flares: ParticleSystem::new(FLARES, seed.wrapping_add(1)),
+            rng: StdRng::seed_from_u64(seed.wrapping_add(2)),
+            next_flare: None,
+            simulated_until: None,
+        }
+    }
+
+    // Advances the particles to `time`. Going back in time leaves them as they are.
+    pub fn update(&mut self, time: f32) {
+        self.next_flare.get_or_insert(time - FIRST_FLARE);
+        for (start, delta) in time_steps(self.simulated_until, time, WARM_UP, MAX_STEP) {
+            self.step(start, delta);
+        }
+        self.simulated_until = Some(self.simulated_until.map_or(time, |last| last.max(time)));
+    }
+
+    fn step(&mut self, time: f32, delta: f32) {
+        let (center, radius) = (self.center, self.radius);
+
+        self.wind.update(delta, |_| Vec3::zeros());
+        self.wind.emit(WIND_RATE, delta, |rng, _| {
+            let direction = random_direction(rng);
+            (center + direction * radius, direction * WIND_SPEED * rng.gen_range(0.7..1.3))
+        });
+
+        self.flares.update(delta, |particle| {
+            let offset = particle.position - center;
+            let distance = offset.magnitude().max(radius * 0.5);
+            -offset / distance * (SURFACE_GRAVITY * (radius / distance).powi(2))
+        });
+
+        // Flares come at random, on average every `FLARE_INTERVAL`
+        let next_flare = self.next_flare.unwrap_or(time);
+        if time + delta >= next_flare {
+            let normal = random_direction(&mut self.rng);
+            self.flares.burst(FLARE_PARTICLES, |rng| {
+                // Particles leave a small patch, mostly upwards and fanning out
+                let spread = random_direction(rng);
+                let spread = spread - normal * normal.dot(&spread);
+                let position = center + (normal + spread * 0.08).normalize() * radius;
+                let velocity = (normal + spread * 0.45) * FLARE_SPEED * rng.gen_range(0.15..1.0);
+                (position, velocity)
+            });
+            let wait = -self.rng.gen::<f32>().max(1e-6).ln() * FLARE_INTERVAL;
+            self.next_flare = Some(next_flare + wait);
+        }
+    }
+
+    // `uniforms` should carry an identity model matrix
+    pub fn render(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        self.wind.render(framebuffer, uniforms);
+        self.flares.render(framebuffer, uniforms);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::mesh::Mesh;
+use crate::vertex::Vertex;
+use crate::Uniforms;
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+use rand::Rng;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+// Radius of the old sphere.obj model, which the planet shaders were tuned for
+pub const SPHERE_RADIUS: f32 = 0.5;
+
+// Icosahedron subdivided `subdivisions` times, 20 * 4^n triangles of nearly
+// equal size. Uvs are equirectangular with the seam split.
+pub fn icosphere(subdivisions: usize, radius: f32) -> Mesh {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let corners = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ];
+    let vertices = corners
+        .iter()
+        .map(|&(x, y, z)| {
+            let normal = Vec3::new(x, y, z).normalize();
+            Vertex::new(normal * radius, normal, Vec2::new(0.0, 0.0))
+        })
+        .collect();
+
+    // Counter-clockwise seen from outside
+    let indices = vec![
+        0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11, //
+        1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8, //
+        3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9, //
+        4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+    ];
+
+    Mesh::new(vertices, indices)
+        .subdivided_sphere(subdivisions)
+        .with_spherical_uvs()
+}
+
+// Latitude/longitude sphere. The first and last columns share positions but
+// not uvs, so the texture seam needs no special handling.
+pub fn uv_sphere(segments: usize, rings: usize, radius: f32) -> Mesh {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+    let mut vertices = Vec::with_capacity((segments + 1) * (rings + 1));
+    let mut indices = Vec::with_capacity(segments * rings * 6);
+
+    for ring in 0..=rings {
+        let v = ring as f32 / rings as f32;
+        let colatitude = v * PI;
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            // Same convention as texture::equirectangular_uv
+            let longitude = (u - 0.5) * 2.0 * PI;
+            let normal = Vec3::new(
+                colatitude.sin() * longitude.cos(),
+                colatitude.cos(),
+                colatitude.sin() * longitude.sin(),
+            );
+            let mut vertex = Vertex::new(normal * radius, normal, Vec2::new(u, v));
+            vertex.tangent = Vec4::new(-longitude.sin(), 0.0, longitude.cos(), 1.0);
+            vertices.push(vertex);
+        }
+    }
+
+    let columns = segments as u32 + 1;
+    for ring in 0..rings as u32 {
+        for segment in 0..segments as u32 {
+            let a = ring * columns + segment;
+            let b = a + columns;
+            // The pole rows would give degenerate triangles
+            if ring != 0 {
+                indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if ring != rings as u32 - 1 {
+                indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+
+    Mesh::new(vertices, indices)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SphereKind {
+    // Evenly sized triangles, best for noise-based surfaces
+    Icosphere,
+    // Rows follow the latitude bands of the gas giants
+    UvSphere,
+}
+
+// Chain of spheres of increasing detail. Each body picks the coarsest one
+// whose triangle edges stay under `edge_pixels` on screen.
+pub struct SphereLod {
+    levels: Vec<Rc<Mesh>>,
+    edge_pixels: f32,
+}
+
+impl SphereLod {
+    pub fn new(kind: SphereKind, max_level: usize, edge_pixels: f32) -> Self {
+        let levels = (0..=max_level)
+            .map(|level| {
+                let mesh = match kind {
+                    SphereKind::Icosphere => icosphere(level, SPHERE_RADIUS),
+                    // Equator edges about as long as the icosphere's of the same level
+                    SphereKind::UvSphere => {
+                        let segments = 6 << level;
+                        uv_sphere(segments, segments / 2, SPHERE_RADIUS)
+                    }
+                };
+                Rc::new(mesh)
+            })
+            .collect();
+
+        SphereLod { levels, edge_pixels }
+    }
+
+    pub fn select(&self, projected_radius: f32) -> &Rc<Mesh> {
+        // Edges span about 1.1 radii on level 0 and halve on every level
+        let edge = projected_radius * 1.1;
+        let level = if edge <= self.edge_pixels {
+            0
+        } else {
+            (edge / self.edge_pixels).log2().ceil() as usize
+        };
+
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+
+    // Picks the level for a sphere drawn with these uniforms
+    pub fn select_for(&self, uniforms: &Uniforms) -> &Rc<Mesh> {
+        self.select(projected_radius(uniforms, SPHERE_RADIUS))
+    }
+}
+
+// Radius in pixels of a model-space sphere at the origin once drawn with
+// `uniforms`. Assumes the uniform scale used by all model matrices here.
+pub fn projected_radius(uniforms: &Uniforms, radius: f32) -> f32 {
+    let scale = Vec3::new(
+        uniforms.model_matrix[(0, 0)],
+        uniforms.model_matrix[(1, 0)],
+        uniforms.model_matrix[(2, 0)],
+    )
+    .magnitude();
+    let center = uniforms.model_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0);
+
+    projected_world_radius(uniforms, &center.xyz(), radius * scale)
+}
+
+// Radius in pixels of a world-space sphere seen through the view and
+// projection of `uniforms`; their model matrix is ignored
+pub fn projected_world_radius(uniforms: &Uniforms, center: &Vec3, radius: f32) -> f32 {
+    // projection[(1, 1)] is 1 / tan(fov / 2) (or 2 / height for orthographic
+    // views) and the viewport maps one unit of ndc to half the screen height
+    let focal = uniforms.projection_matrix[(1, 1)].abs();
+    let half_height = uniforms.viewport_matrix[(1, 1)].abs();
+
+    // Orthographic projections keep w = 1, so size does not fall off with distance
+    if uniforms.projection_matrix[(3, 3)] != 0.0 {
+        return radius * focal * half_height;
+    }
+
+    let center = uniforms.view_matrix * Vec4::new(center.x, center.y, center.z, 1.0);
+    if center.xyz().magnitude() <= radius {
+        // The camera is inside or touching the sphere
+        return f32::INFINITY;
+    }
+
+    // Depth along the view axis, not the distance: a sphere beside the camera
+    // is close but may still be tiny on screen
+    let distance = -center.z;
+    if distance <= 0.0 {
+        // Centre behind the camera: the coarsest mesh is enough for whatever
+        // part of it reaches into view
+        return 0.0;
+    }
+
+    radius / distance * focal * half_height
+}
+
+// Unit vector uniformly distributed over the sphere: uniform azimuth and
+// uniform height
+pub fn random_direction<R: Rng>(rng: &mut R) -> Vec3 {
+    let theta = rng.gen::<f32>() * 2.0 * PI;
+    let y = rng.gen_range(-1.0f32..1.0);
+    let ring = (1.0 - y * y).sqrt();
+    Vec3::new(ring * theta.cos(), y, ring * theta.sin())
+}
@@ -0,0 +1,129 @@
+use crate::color::Color;
+use rand::Rng;
+
+// Harvard spectral classes, hottest to coolest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpectralClass {
+    O,
+    B,
+    A,
+    F,
+    G,
+    K,
+    M,
+}
+
+// Share of each class among the stars visible to the naked eye, which leans
+// towards hot, luminous stars much more than the galaxy as a whole
+const CLASS_FREQUENCIES: [(SpectralClass, f32); 7] = [
+    (SpectralClass::O, 0.005),
+    (SpectralClass::B, 0.10),
+    (SpectralClass::A, 0.22),
+    (SpectralClass::F, 0.19),
+    (SpectralClass::G, 0.14),
+    (SpectralClass::K, 0.26),
+    (SpectralClass::M, 0.085),
+];
+
+// Magnitude range of the generated sky: Sirius-like down to the naked-eye limit
+const BRIGHTEST_MAGNITUDE: f32 = -1.5;
+const FAINTEST_MAGNITUDE: f32 = 6.5;
+// Growth of star counts with magnitude, N(< m) ~ 10^(slope * m)
+const MAGNITUDE_SLOPE: f32 = 0.5;
+// Magnitude drawn at full intensity on a single pixel
+const REFERENCE_MAGNITUDE: f32 = 3.5;
+// Compresses the huge flux range into what a screen can show
+const DISPLAY_GAMMA: f32 = 0.45;
+
+impl SpectralClass {
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        let mut pick = rng.gen::<f32>() * CLASS_FREQUENCIES.iter().map(|(_, share)| share).sum::<f32>();
+        for (class, share) in CLASS_FREQUENCIES {
+            if pick < share {
+                return class;
+            }
+            pick -= share;
+        }
+        SpectralClass::M
+    }
+
+    // Effective surface temperature range in kelvin
+    pub fn temperature_range(self) -> (f32, f32) {
+        match self {
+            SpectralClass::O => (30000.0, 45000.0),
+            SpectralClass::B => (10000.0, 30000.0),
+            SpectralClass::A => (7500.0, 10000.0),
+            SpectralClass::F => (6000.0, 7500.0),
+            SpectralClass::G => (5200.0, 6000.0),
+            SpectralClass::K => (3700.0, 5200.0),
+            SpectralClass::M => (2400.0, 3700.0),
+        }
+    }
+
+    pub fn random_temperature<R: Rng>(self, rng: &mut R) -> f32 {
+        let (min, max) = self.temperature_range();
+        rng.gen_range(min..max)
+    }
+}
+
+// Apparent color of a blackbody at `temperature` kelvin, with the brightest
+// channel at full value. Fit to the CIE color matching curves by Tanner
+// Helland, good from 1000 K to 40000 K.
+pub fn blackbody_color(temperature: f32) -> Color {
+    let t = temperature.clamp(1000.0, 40000.0) / 100.0;
+
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let green = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12216 * (t - 60.0).powf(-0.075514846)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+
+    let (red, green, blue) = (red.clamp(0.0, 255.0), green.clamp(0.0, 255.0), blue.clamp(0.0, 255.0));
+    let peak = red.max(green).max(blue).max(1.0);
+    Color::from_float(red / peak, green / peak, blue / peak)
+}
+
+// Temperature in kelvin from the B-V color index (Ballesteros 2012)
+pub fn color_index_temperature(color_index: f32) -> f32 {
+    let bv = color_index.clamp(-0.4, 2.0);
+    4600.0 * (1.0 / (0.92 * bv + 1.7) + 1.0 / (0.92 * bv + 0.62))
+}
+
+// Apparent magnitude following the star count law, so faint stars vastly
+// outnumber bright ones. None come out brighter than `brightest`.
+pub fn random_magnitude<R: Rng>(rng: &mut R, brightest: f32) -> f32 {
+    let low = star_count_at(brightest);
+    let high = star_count_at(FAINTEST_MAGNITUDE);
+    let u = rng.gen::<f32>();
+    (low + u * (high - low)).log10() / MAGNITUDE_SLOPE
+}
+
+// Share of a generated sky's stars that are fainter than `magnitude`
+pub fn fainter_share(magnitude: f32) -> f32 {
+    let total = star_count_at(FAINTEST_MAGNITUDE) - star_count_at(BRIGHTEST_MAGNITUDE);
+    (star_count_at(FAINTEST_MAGNITUDE) - star_count_at(magnitude)) / total
+}
+
+// Relative number of stars brighter than `magnitude`, within the generated range
+fn star_count_at(magnitude: f32) -> f32 {
+    10f32.powf(MAGNITUDE_SLOPE * magnitude.clamp(BRIGHTEST_MAGNITUDE, FAINTEST_MAGNITUDE))
+}
+
+// Screen intensity of a star, 1.0 at the reference magnitude and above it
+// for brighter stars
+pub fn magnitude_intensity(magnitude: f32) -> f32 {
+    let flux = 10f32.powf(-0.4 * (magnitude - REFERENCE_MAGNITUDE));
+    flux.powf(DISPLAY_GAMMA)
+}
@@ -0,0 +1,432 @@
+use crate::color::Color;
+use nalgebra_glm::{Vec2, Vec3};
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    Trilinear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+}
+
+impl MipLevel {
+    fn texel(&self, x: i64, y: i64, wrap_u: WrapMode, wrap_v: WrapMode) -> Vec3 {
+        let x = wrap(x, self.width, wrap_u);
+        let y = wrap(y, self.height, wrap_v);
+        self.texels[y * self.width + x]
+    }
+
+    fn sample_nearest(&self, uv: Vec2, wrap_u: WrapMode, wrap_v: WrapMode) -> Vec3 {
+        let x = (uv.x * self.width as f32).floor() as i64;
+        let y = (uv.y * self.height as f32).floor() as i64;
+        self.texel(x, y, wrap_u, wrap_v)
+    }
+
+    fn sample_bilinear(&self, uv: Vec2, wrap_u: WrapMode, wrap_v: WrapMode) -> Vec3 {
+        // Texel centers sit at half-integer coordinates
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0, wrap_u, wrap_v) * (1.0 - tx)
+            + self.texel(x0 + 1, y0, wrap_u, wrap_v) * tx;
+        let bottom = self.texel(x0, y0 + 1, wrap_u, wrap_v) * (1.0 - tx)
+            + self.texel(x0 + 1, y0 + 1, wrap_u, wrap_v) * tx;
+
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // 2x2 box filter; odd sizes fold the last row/column into the previous one
+    fn downsample(&self) -> MipLevel {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let x0 = (x * 2).min(self.width - 1);
+                let y0 = (y * 2).min(self.height - 1);
+                let x1 = (x * 2 + 1).min(self.width - 1);
+                let y1 = (y * 2 + 1).min(self.height - 1);
+
+                // Averaged in linear light, or dark detail would darken
+                // every smaller level
+                let sum = srgb_to_linear(self.texels[y0 * self.width + x0])
+                    + srgb_to_linear(self.texels[y0 * self.width + x1])
+                    + srgb_to_linear(self.texels[y1 * self.width + x0])
+                    + srgb_to_linear(self.texels[y1 * self.width + x1]);
+                texels.push(linear_to_srgb(sum * 0.25));
+            }
+        }
+
+        MipLevel { width, height, texels }
+    }
+}
+
+fn srgb_to_linear(color: Vec3) -> Vec3 {
+    color.map(|c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+fn linear_to_srgb(color: Vec3) -> Vec3 {
+    color.map(|c| {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+fn wrap(coord: i64, size: usize, mode: WrapMode) -> usize {
+    let size = size as i64;
+    match mode {
+        WrapMode::Repeat => coord.rem_euclid(size) as usize,
+        WrapMode::ClampToEdge => coord.clamp(0, size - 1) as usize,
+        WrapMode::MirroredRepeat => {
+            let period = coord.rem_euclid(size * 2);
+            if period < size {
+                period as usize
+            } else {
+                (size * 2 - 1 - period) as usize
+            }
+        }
+    }
+}
+
+// RGB texture with float texels in 0..1 and an optional mip chain. Texels
+// keep the sRGB encoding of the image (or of the generated map), which is
+// what the shaders expect; only mip generation works in linear light.
+pub struct Texture {
+    levels: Vec<MipLevel>,
+    pub filter: FilterMode,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+}
+
+impl Texture {
+    pub fn load(path: &str) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.to_rgb32f();
+        let (width, height) = image.dimensions();
+        let texels = image
+            .pixels()
+            .map(|p| Vec3::new(p[0], p[1], p[2]))
+            .collect();
+
+        Ok(Texture::from_texels(width as usize, height as usize, texels))
+    }
+
+    pub fn from_texels(width: usize, height: usize, texels: Vec<Vec3>) -> Self {
+        assert_eq!(texels.len(), width * height, "texel count does not match size");
+
+        Texture {
+            levels: vec![MipLevel { width, height, texels }],
+            filter: FilterMode::Bilinear,
+            // Equirectangular maps wrap around in longitude but not in latitude
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::ClampToEdge,
+        }
+    }
+
+    // Generated textures: the closure receives the uv at each texel center
+    pub fn from_fn<F>(width: usize, height: usize, f: F) -> Self
+    where
+        F: Fn(Vec2) -> Vec3,
+    {
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let uv = Vec2::new(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                texels.push(f(uv));
+            }
+        }
+
+        Texture::from_texels(width, height, texels)
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    pub fn with_filter(mut self, filter: FilterMode) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
+    }
+
+    // Builds the full mip chain down to 1x1
+    pub fn generate_mipmaps(mut self) -> Self {
+        self.levels.truncate(1);
+        while {
+            let last = self.levels.last().unwrap();
+            last.width > 1 || last.height > 1
+        } {
+            let next = self.levels.last().unwrap().downsample();
+            self.levels.push(next);
+        }
+        self
+    }
+
+    // Samples the texture without level-of-detail information, trilinear
+    // filtering falls back to bilinear on the base level
+    pub fn sample(&self, uv: Vec2) -> Vec3 {
+        self.sample_level(uv, 0.0)
+    }
+
+    // `uv_footprint` is how much uv space one screen pixel covers, as stored in
+    // `Fragment::uv_footprint`. It selects the mip level for trilinear filtering.
+    pub fn sample_footprint(&self, uv: Vec2, uv_footprint: f32) -> Vec3 {
+        let texels_per_pixel = uv_footprint * self.width().max(self.height()) as f32;
+        let lod = texels_per_pixel.max(1e-6).log2().max(0.0);
+        self.sample_level(uv, lod)
+    }
+
+    pub fn sample_level(&self, uv: Vec2, lod: f32) -> Vec3 {
+        match self.filter {
+            FilterMode::Nearest => self.levels[0].sample_nearest(uv, self.wrap_u, self.wrap_v),
+            FilterMode::Bilinear => self.levels[0].sample_bilinear(uv, self.wrap_u, self.wrap_v),
+            FilterMode::Trilinear => {
+                let max_level = (self.levels.len() - 1) as f32;
+                let lod = lod.clamp(0.0, max_level);
+                let lower = lod.floor() as usize;
+                let upper = (lower + 1).min(self.levels.len() - 1);
+                let t = lod - lower as f32;
+
+                let a = self.levels[lower].sample_bilinear(uv, self.wrap_u, self.wrap_v);
+                if t <= 0.0 || upper == lower {
+                    return a;
+                }
+                let b = self.levels[upper].sample_bilinear(uv, self.wrap_u, self.wrap_v);
+                a * (1.0 - t) + b * t
+            }
+        }
+    }
+
+    // Base level texel at integer coordinates, wrapped like samples are
+    pub fn texel(&self, x: i64, y: i64) -> Vec3 {
+        self.levels[0].texel(x, y, self.wrap_u, self.wrap_v)
+    }
+
+    pub fn sample_color(&self, uv: Vec2, uv_footprint: f32) -> Color {
+        let texel = self.sample_footprint(uv, uv_footprint);
+        Color::from_float(texel.x, texel.y, texel.z)
+    }
+
+    // Writes the base level as an 8-bit RGB image, the format comes from the
+    // file extension
+    pub fn save(&self, path: &str) -> Result<(), image::ImageError> {
+        let level = &self.levels[0];
+        let mut image = image::RgbImage::new(level.width as u32, level.height as u32);
+        for (pixel, texel) in image.pixels_mut().zip(&level.texels) {
+            let color = Color::from_float(texel.x, texel.y, texel.z).to_hex();
+            *pixel = image::Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+        }
+        image.save(path)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+pub const CUBE_FACES: [CubeFace; 6] = [
+    CubeFace::PositiveX,
+    CubeFace::NegativeX,
+    CubeFace::PositiveY,
+    CubeFace::NegativeY,
+    CubeFace::PositiveZ,
+    CubeFace::NegativeZ,
+];
+
+impl CubeFace {
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            CubeFace::PositiveX => "px",
+            CubeFace::NegativeX => "nx",
+            CubeFace::PositiveY => "py",
+            CubeFace::NegativeY => "ny",
+            CubeFace::PositiveZ => "pz",
+            CubeFace::NegativeZ => "nz",
+        }
+    }
+
+    // Direction through a point of the face, same layout as OpenGL cube maps
+    pub fn direction(&self, uv: Vec2) -> Vec3 {
+        let s = uv.x * 2.0 - 1.0;
+        let t = uv.y * 2.0 - 1.0;
+        let direction = match self {
+            CubeFace::PositiveX => Vec3::new(1.0, -t, -s),
+            CubeFace::NegativeX => Vec3::new(-1.0, -t, s),
+            CubeFace::PositiveY => Vec3::new(s, 1.0, t),
+            CubeFace::NegativeY => Vec3::new(s, -1.0, -t),
+            CubeFace::PositiveZ => Vec3::new(s, -t, 1.0),
+            CubeFace::NegativeZ => Vec3::new(-s, -t, -1.0),
+        };
+        direction.normalize()
+    }
+
+    // Face hit by a direction and the uv inside that face
+    pub fn from_direction(direction: &Vec3) -> (CubeFace, Vec2) {
+        let (ax, ay, az) = (direction.x.abs(), direction.y.abs(), direction.z.abs());
+        let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+            if direction.x > 0.0 {
+                (CubeFace::PositiveX, -direction.z, -direction.y, ax)
+            } else {
+                (CubeFace::NegativeX, direction.z, -direction.y, ax)
+            }
+        } else if ay >= az {
+            if direction.y > 0.0 {
+                (CubeFace::PositiveY, direction.x, direction.z, ay)
+            } else {
+                (CubeFace::NegativeY, direction.x, -direction.z, ay)
+            }
+        } else if direction.z > 0.0 {
+            (CubeFace::PositiveZ, direction.x, -direction.y, az)
+        } else {
+            (CubeFace::NegativeZ, -direction.x, -direction.y, az)
+        };
+
+        let ma = ma.max(f32::EPSILON);
+        (face, Vec2::new((sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5))
+    }
+}
+
+// Six square faces in CUBE_FACES order, sampled by direction
+pub struct CubeMap {
+    faces: Vec<Texture>,
+}
+
+impl CubeMap {
+    pub fn from_faces(faces: Vec<Texture>) -> Self {
+        assert_eq!(faces.len(), 6, "a cube map needs six faces");
+        let faces = faces
+            .into_iter()
+            .map(|face| face.with_wrap(WrapMode::ClampToEdge, WrapMode::ClampToEdge))
+            .collect();
+        CubeMap { faces }
+    }
+
+    // The closure receives the direction through each texel center
+    pub fn from_fn<F>(size: usize, f: F) -> Self
+    where
+        F: Fn(Vec3) -> Vec3,
+    {
+        let faces = CUBE_FACES
+            .iter()
+            .map(|face| Texture::from_fn(size, size, |uv| f(face.direction(uv))))
+            .collect();
+        CubeMap::from_faces(faces)
+    }
+
+    // Width and height of every face
+    pub fn size(&self) -> usize {
+        self.faces[0].width()
+    }
+
+    pub fn face(&self, face: CubeFace) -> &Texture {
+        &self.faces[face as usize]
+    }
+
+    // Bilinear samples near an edge blend in texels from the neighbouring
+    // face instead of clamping, so the cube shows no seams
+    pub fn sample(&self, direction: &Vec3) -> Vec3 {
+        let (face, uv) = CubeFace::from_direction(direction);
+        let texture = self.face(face);
+        if texture.filter == FilterMode::Nearest {
+            return texture.sample(uv);
+        }
+
+        // Texel centers sit at half-integer coordinates
+        let size = texture.width() as f32;
+        let x = uv.x * size - 0.5;
+        let y = uv.y * size - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(face, x0, y0) * (1.0 - tx) + self.texel(face, x0 + 1, y0) * tx;
+        let bottom = self.texel(face, x0, y0 + 1) * (1.0 - tx) + self.texel(face, x0 + 1, y0 + 1) * tx;
+
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // Texel of `face` at integer coordinates. Those past the edge are followed
+    // around the cube onto the face their direction actually hits.
+    fn texel(&self, face: CubeFace, x: i64, y: i64) -> Vec3 {
+        let size = self.face(face).width() as i64;
+        if (0..size).contains(&x) && (0..size).contains(&y) {
+            return self.face(face).texel(x, y);
+        }
+
+        let uv = Vec2::new((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32);
+        let (neighbour, uv) = CubeFace::from_direction(&face.direction(uv));
+        let texture = self.face(neighbour);
+        let x = (uv.x * texture.width() as f32).floor() as i64;
+        let y = (uv.y * texture.height() as f32).floor() as i64;
+        texture.texel(x, y)
+    }
+
+    // Faces are stored as `<dir>/<name>_<px|nx|py|ny|pz|nz>.png`
+    pub fn load(dir: &str, name: &str) -> Result<Self, image::ImageError> {
+        let faces = CUBE_FACES
+            .iter()
+            .map(|face| Texture::load(&format!("{}/{}_{}.png", dir, name, face.suffix())))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CubeMap::from_faces(faces))
+    }
+
+    pub fn save(&self, dir: &str, name: &str) -> Result<(), image::ImageError> {
+        for face in CUBE_FACES {
+            self.face(face)
+                .save(&format!("{}/{}_{}.png", dir, name, face.suffix()))?;
+        }
+        Ok(())
+    }
+}
+
+// Equirectangular uv for a direction from the center of a sphere. u grows
+// with longitude around +Y and v goes from the north pole (0) to the south (1).
+pub fn equirectangular_uv(direction: &Vec3) -> Vec2 {
+    let direction = direction.normalize();
+    let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+    Vec2::new(u, v)
+}
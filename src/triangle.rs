@@ -1,18 +1,24 @@
-use nalgebra_glm::{Vec3, dot, Vec2};
+use nalgebra_glm::{Vec3, Vec4, dot, Vec2};
 use crate::fragment::Fragment;
 use crate::vertex::{self, Vertex};
 use crate::color::Color;
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, width: usize, height: usize) -> Vec<Fragment> {
   let mut fragments = Vec::new();
   let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
 
   let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+  // Only the part of the triangle on screen is rasterized
+  let (min_x, min_y) = (min_x.max(0), min_y.max(0));
+  let (max_x, max_y) = (max_x.min(width as i32 - 1), max_y.min(height as i32 - 1));
 
   let light_dir = Vec3::new(0.0, 0.0, 1.0);
 
   let triangle_area = edge_function(&a, &b, &c);
 
+  // How much uv space one pixel covers, used to pick mip levels
+  let uv_footprint = uv_footprint(v1.tex_coords, v2.tex_coords, v3.tex_coords, triangle_area);
+
   // Iterate over each pixel in the bounding box
   for y in min_y..=max_y {
     for x in min_x..=max_x {
@@ -30,6 +36,12 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
         let normal = v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3;
         let normal = normal.normalize();
 
+        // Interpolate tangent, the handedness is the same for the whole triangle
+        let tangent = v1.transformed_tangent.xyz() * w1
+          + v2.transformed_tangent.xyz() * w2
+          + v3.transformed_tangent.xyz() * w3;
+        let tangent = Vec4::new(tangent.x, tangent.y, tangent.z, v1.transformed_tangent.w);
+
         // Calculate lighting intensity
         let intensity = dot(&normal, &light_dir).max(0.0);
 
@@ -42,14 +54,20 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
         // Positions of the original vertex
         let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
 
-        fragments.push(Fragment::new(
-            Vec2::new(x as f32, y as f32),
-            color,
-            depth,
-            normal,
-            intensity,
-            vertex_position,
-        ));
+        // Interpolate texture coordinates
+        let tex_coords = v1.tex_coords * w1 + v2.tex_coords * w2 + v3.tex_coords * w3;
+
+        fragments.push(Fragment {
+          position: Vec2::new(x as f32, y as f32),
+          color,
+          depth,
+          normal,
+          tangent,
+          intensity,
+          vertex_position,
+          tex_coords,
+          uv_footprint,
+        });
       }
     }
   }
@@ -74,6 +92,18 @@ fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) ->
     (w1, w2, w3)
 }
 
+fn uv_footprint(uv1: Vec2, uv2: Vec2, uv3: Vec2, screen_area: f32) -> f32 {
+    let e1 = uv2 - uv1;
+    let e2 = uv3 - uv1;
+    let uv_area = (e1.x * e2.y - e1.y * e2.x).abs();
+
+    if screen_area.abs() > f32::EPSILON {
+        (uv_area / screen_area.abs()).sqrt()
+    } else {
+        0.0
+    }
+}
+
 fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
     (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
 }
@@ -1,4 +1,4 @@
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 use crate::color::Color;
 
 #[derive(Clone, Debug)]
@@ -6,9 +6,14 @@ pub struct Vertex {
   pub position: Vec3,
   pub normal: Vec3,
   pub tex_coords: Vec2,
+  // xyz points along +u, w is the handedness of the bitangent (+v)
+  pub tangent: Vec4,
   pub color: Color,
+  // Before the perspective divide, kept so triangles can be clipped
+  pub clip_position: Vec4,
   pub transformed_position: Vec3,
   pub transformed_normal: Vec3,
+  pub transformed_tangent: Vec4,
 }
 
 impl Vertex {
@@ -17,9 +22,12 @@ impl Vertex {
       position,
       normal,
       tex_coords,
+      tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
       color: Color::black(),
+      clip_position: Vec4::new(position.x, position.y, position.z, 1.0),
       transformed_position: position,
       transformed_normal: normal,
+      transformed_tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
     }
   }
 
@@ -28,9 +36,12 @@ impl Vertex {
       position,
       normal: Vec3::new(0.0, 0.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
+      tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
       color,
+      clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
       transformed_normal: Vec3::new(0.0, 0.0, 0.0),
+      transformed_tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
     }
   }
 
@@ -46,9 +57,12 @@ impl Default for Vertex {
       position: Vec3::new(0.0, 0.0, 0.0),
       normal: Vec3::new(0.0, 1.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
+      tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
       color: Color::black(),
+      clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
       transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+      transformed_tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
     }
   }
 }